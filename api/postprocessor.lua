@@ -0,0 +1,14 @@
+-- Searched Lua API Definition
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+--- @meta searched
+
+--- Add a postprocessor
+---
+--- Postprocessors run on the ranked results, in order of their names. They
+--- can rewrite, drop or annotate results and return the ones to show.
+---
+--- @param name string
+--- @param callback fun(results: Result[], options: table<string, string>): Result[]
+function add_postprocessor(name, callback) end
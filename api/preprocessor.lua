@@ -4,6 +4,21 @@
 
 --- @meta searched
 
+--- @class Preprocessed
+--- What to do with the search, every field is optional
+---
+--- @field public providers string[]? Ask these providers instead
+--- @field public redirect string? Send the user to this URL instead of searching
+
+--- Add a preprocessor
+---
+--- Preprocessors run before any provider is asked, in order of their names.
+--- They can rewrite the query in place by setting its fields.
+---
+--- @param name string
+--- @param callback fun(query: Query, providers: string[]): Preprocessed?
+function add_preprocessor(name, callback) end
+
 --- Evaluate an expression with Fend
 ---
 --- @param input string
@@ -9,6 +9,9 @@
 --- @field public general? GeneralResult
 --- @field public forum? ForumResult
 --- @field public image? ImageResult
+--- @field public date? string When the page was published, as RFC 3339, `YYYY-MM-DD` or Unix seconds
+--- @field public positions table<string, integer> Position each provider gave the result, starting from 1
+--- @field public annotations string[] Notes added by postprocessors
 Result = {}
 
 --- @class GeneralResult
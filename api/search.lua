@@ -4,10 +4,21 @@
 
 --- @meta searched
 
+--- @class EngineResponse
+--- Results along with what else the provider said, every field but `results` is optional
+---
+--- @field public results [Result]
+--- @field public correction string? The query the provider thinks was meant
+--- @field public related string[]? Other searches the provider suggests
+--- @field public total number? How many results the provider estimates it has
+
 --- Add a engine
 ---
+--- Engines return their results, or an `EngineResponse` when the provider
+--- says more than that.
+---
 --- @param name string
---- @param callback fun(client: Client, query: Query, options: table<string, string|number|boolean>): [Result]
+--- @param callback fun(client: Client, query: Query, options: table<string, string|number|boolean>): [Result]|EngineResponse
 function add_engine(name, callback) end
 
 --- Stringify parameters
@@ -37,13 +48,31 @@ Kind = {
 }
 
 --- @class Query
---- A search query
+--- A search query, preprocessors can change its fields
 ---
 --- @field public query string
---- @field public page number
---- @field public safe string
+--- @field public kind Kind
+--- @field public page number Starts at 1, engines get it counted from where their provider starts
+--- @field public safe string Engines only get levels their provider supports
+--- @field public language string? Preferred language of results, a lowercase ISO 639-1 code like `de`
+--- @field public region string? Preferred region of results, an uppercase ISO 3166-1 code like `AT`
+--- @field public time_range 'day'|'week'|'month'|'year'|nil Only show results from this far back
+--- @field public since number? Unix seconds the time range starts at, read only
+--- @field public terms [Term] The query parsed into words, phrases and operators
+--- @field public plain string The words and phrases to look for, without any operators
 Query = {}
 
+--- @class Term
+--- A part of a query. Results that go against `site`, `filetype` and
+--- `intitle` terms, or that have excluded words, are dropped after merging,
+--- so engines can leave out whatever their provider can't do.
+---
+--- @field public type 'word'|'phrase'|'site'|'filetype'|'intitle'|'or'
+--- @field public value string Not set on `or` groups
+--- @field public negated boolean Written with a leading `-`, results must not match it
+--- @field public terms [Term]? Members of an `or` group, any of them can match
+Term = {}
+
 --- @class Element
 ---
 --- @field public inner_html string
@@ -57,7 +86,8 @@ Element = {}
 function Element:attr(attr) end
 
 --- @class Client
---- An HTTP client
+--- An HTTP client. Requests send `Accept-Language` for the query's
+--- language and region unless their headers set it.
 Client = {}
 
 --- Start building a request
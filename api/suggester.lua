@@ -0,0 +1,16 @@
+-- Searched Lua API Definition
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+--- @meta searched
+
+--- Add a suggester
+---
+--- Suggesters complete queries as they are typed, for browsers' address
+--- bars. They get the partly typed query and return whole queries, best
+--- first. They are given little time, so one request is about all they
+--- can make.
+---
+--- @param name string
+--- @param callback fun(client: Client, query: Query): string[]
+function add_suggester(name, callback) end
@@ -24,6 +24,9 @@ function Url.parse_with_params(input, query_params) end
 
 --- Build a Url from a template string
 ---
+--- Raises an error if the template's braces don't match up, or a `{key}`
+--- in it has no value.
+---
 --- @param template string
 --- @param values table<string, string>
 ---
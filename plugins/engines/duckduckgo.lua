@@ -17,7 +17,11 @@ add_engine('duckduckgo', function(client, query, _)
 		offset = 20 + (query.page - 2) * 50
 	end
 
-	local form_data = { q = query.query }
+	local form_data = { q = query.query, kl = duckduckgo_region(query) }
+	if query.time_range then
+		-- d, w, m or y
+		form_data.df = query.time_range:sub(1, 1)
+	end
 	if query.page > 1 then
 		form_data = {
 			s = tostring(offset),
@@ -27,7 +31,8 @@ add_engine('duckduckgo', function(client, query, _)
 			dc = tostring(offset + 1),
 			api = 'd.js',
 			vqd = '',
-			kl = 'wt-wt',
+			kl = duckduckgo_region(query),
+			df = form_data.df,
 		}
 	end
 
@@ -64,5 +69,11 @@ add_engine('duckduckgo', function(client, query, _)
 		end
 	end
 
-	return results
+	-- "Including results for <correction>. Search only for <query>?"
+	local correction = doc:select('#did_you_mean a')[1]
+
+	return {
+		results = results,
+		correction = correction and correction.inner_text,
+	}
 end)
@@ -12,6 +12,9 @@ add_engine('google', function(client, query, _)
 		async = 'use_ac:true,_fmt:prog',
 		start = tostring(offset),
 		q = query.query,
+		hl = query.language or 'en',
+		gl = query.region,
+		tbs = query.time_range and ('qdr:' .. query.time_range:sub(1, 1)) or nil,
 	}):string()
 
 	local doc = client
@@ -40,9 +40,13 @@ local function get_key(data, key)
 end
 
 add_engine('json', function(client, query, opts)
+	-- These APIs don't know any operators, they're checked on the results instead
+	-- Providers with sites per language can use {language} in their URLs
+	local language = query.language or tostring(opts.language or 'en')
 	local url = Url.from_template(tostring(opts.url), {
-		query = query.query,
+		query = query.plain,
 		page = tostring(query.page),
+		language = language,
 	}):string()
 
 	local data = client
@@ -66,7 +70,8 @@ add_engine('json', function(client, query, opts)
 
 			if result_url ~= nil then
 				if opts.url_prefix then
-					result_url = opts.url_prefix .. result_url
+					local prefix = string.gsub(tostring(opts.url_prefix), '{language}', language)
+					result_url = prefix .. result_url
 				end
 
 				results[i] = {
@@ -4,10 +4,10 @@
 
 add_engine('mediawiki', function(client, query, opts)
 	local url = Url.from_template(tostring(opts.url), {
-		query = query.query,
+		query = query.plain,
 	}):string()
 
-	local res = client:get(url, {})
+	local res = client:req('GET', url):send()
 	local data = parse_json(res)
 
 	--- @type [Result]
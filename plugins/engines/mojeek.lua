@@ -3,7 +3,7 @@
 -- (c) 2024 Dragynfruit
 
 add_engine('mojeek', function(client, query, _)
-	local offset
+	local offset = 0
 	if query.page == 2 then
 		offset = (query.page - 1) * 20
 	elseif query.page > 2 then
@@ -39,5 +39,11 @@ add_engine('mojeek', function(client, query, _)
 		table.insert(results, result)
 	end
 
-	return results
+	-- "Did you mean <correction>?"
+	local correction = doc:select('.spell a')[1]
+
+	return {
+		results = results,
+		correction = correction and correction.inner_text,
+	}
 end)
@@ -11,11 +11,14 @@ add_engine('qwant', function(client, query, opts)
 	end
 
 	local url = Url.from_template(
-		'https://api.qwant.com/v3/search/web?q={query}&count=10&locale=en_US&offset={offset}&device=desktop&tgp=3&safesearch={safesearch}&displayed=true&llm=false',
+		'https://api.qwant.com/v3/search/web?q={query}&count=10&locale={locale}&freshness={freshness}&offset={offset}&device=desktop&tgp=3&safesearch={safesearch}&displayed=true&llm=false',
 		{
 			query = query.query,
-			offset = tostring((query.page - 1) * 10),
+			offset = tostring(query.page * 10),
 			safesearch = tostring(safesearch),
+			locale = qwant_locale(query),
+			-- Qwant has no past year, that's left to filtering by date
+			freshness = (query.time_range and query.time_range ~= 'year') and query.time_range or 'all',
 		}
 	):string()
 
@@ -1,23 +1,43 @@
+--- Split query terms into free text and words that have to be in the title
+---
+--- @param terms [Term]
+--- @return string, string
+local function translate(terms)
+	local text, title = {}, {}
+	for _, term in ipairs(terms) do
+		if not term.negated then
+			if term.type == 'word' or term.type == 'phrase' then
+				table.insert(text, term.value)
+			elseif term.type == 'intitle' then
+				table.insert(title, term.value)
+			end
+		end
+	end
+	return table.concat(text, ' '), table.concat(title, ' ')
+end
+
 add_engine('stackexchange', function(client, query, opts)
-	local res = client:get(
-		'https://api.stackexchange.com/2.3/search/advanced?q='
-			.. query.query
-			.. '&page='
-			.. query.page
-			.. '&site='
-			.. opts.site,
-		{}
-	)
+	local text, title = translate(query.terms)
+	local url = Url.from_template(
+		'https://api.stackexchange.com/2.3/search/advanced?q={query}&title={title}&page={page}&site={site}&fromdate={fromdate}',
+		{
+			query = text,
+			title = title,
+			page = tostring(query.page),
+			site = tostring(opts.site),
+			fromdate = query.since and tostring(query.since) or '',
+		}
+	):string()
 
-	print(res)
-	local data = parse_json(res)
+	local data = parse_json(client:req('GET', url):send())
 
 	--- @type [Result]
 	local results = {}
-	for i, item in ipairs(data) do
+	for i, item in ipairs(data.items or {}) do
 		results[i] = {
 			url = item['link'],
 			title = item['title'],
+			date = tostring(item['creation_date']),
 			general = {
 				snippet = table.concat(item['tags'], ' '),
 			},
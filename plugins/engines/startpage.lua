@@ -26,7 +26,8 @@ add_engine('startpage', function(client, query, _)
 	local results = {}
 	for i, link in ipairs(links) do
 		local url = link:attr('href')
-		local title = link.inner_html
+		-- The title is wrapped in an h2
+		local title = link.inner_text
 		local result = {
 			url = url,
 			title = title,
@@ -6,7 +6,7 @@ add_engine('stract', function(client, query, opts)
 	-- Build request body
 	local body = {
 		query = query.query,
-		page = query.page - 1, -- Stract uses 0-based pagination
+		page = query.page,
 		numResults = 10,
 		flattenResponse = true,
 		countResultsExact = true,
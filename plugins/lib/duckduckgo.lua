@@ -0,0 +1,16 @@
+-- DuckDuckGo helpers for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+--- DuckDuckGo's region code, like `de-de` or `us-en`
+---
+--- @param query Query
+--- @return string
+function duckduckgo_region(query)
+	if not query.region then
+		return 'wt-wt'
+	end
+	-- DuckDuckGo calls the United Kingdom uk
+	local region = query.region == 'GB' and 'uk' or query.region:lower()
+	return region .. '-' .. (query.language or 'en')
+end
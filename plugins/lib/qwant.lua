@@ -0,0 +1,36 @@
+-- Qwant helpers for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+-- Locales Qwant takes, anything else is an error
+local LOCALES = {}
+for _, locale in ipairs({
+	'bg_BG', 'br_FR', 'ca_ES', 'co_FR', 'cs_CZ', 'cy_GB', 'da_DK', 'de_AT', 'de_CH', 'de_DE',
+	'el_GR', 'en_AU', 'en_CA', 'en_GB', 'en_IE', 'en_MY', 'en_NZ', 'en_US', 'es_AR', 'es_CL',
+	'es_CO', 'es_ES', 'es_MX', 'es_US', 'et_EE', 'eu_ES', 'fi_FI', 'fr_BE', 'fr_CA', 'fr_CH',
+	'fr_FR', 'gd_GB', 'he_IL', 'hu_HU', 'it_CH', 'it_IT', 'ja_JP', 'ko_KR', 'nb_NO', 'nl_BE',
+	'nl_NL', 'pl_PL', 'pt_BR', 'pt_PT', 'ro_RO', 'sv_SE', 'th_TH', 'zh_CN', 'zh_HK', 'zh_TW',
+}) do
+	LOCALES[locale] = true
+end
+
+-- Where each language is spoken the most, for queries without a region
+local DEFAULT_LOCALES = {
+	bg = 'bg_BG', br = 'br_FR', ca = 'ca_ES', co = 'co_FR', cs = 'cs_CZ', cy = 'cy_GB',
+	da = 'da_DK', de = 'de_DE', el = 'el_GR', en = 'en_US', es = 'es_ES', et = 'et_EE',
+	eu = 'eu_ES', fi = 'fi_FI', fr = 'fr_FR', gd = 'gd_GB', he = 'he_IL', hu = 'hu_HU',
+	it = 'it_IT', ja = 'ja_JP', ko = 'ko_KR', nb = 'nb_NO', no = 'nb_NO', nl = 'nl_NL',
+	pl = 'pl_PL', pt = 'pt_PT', ro = 'ro_RO', sv = 'sv_SE', th = 'th_TH', zh = 'zh_CN',
+}
+
+--- The query's language and region as a Qwant locale, en_US if it has none
+---
+--- @param query Query
+--- @return string
+function qwant_locale(query)
+	local language = query.language or 'en'
+	if query.region and LOCALES[language .. '_' .. query.region] then
+		return language .. '_' .. query.region
+	end
+	return DEFAULT_LOCALES[language] or 'en_US'
+end
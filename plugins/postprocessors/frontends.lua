@@ -0,0 +1,67 @@
+-- Alternative frontend rewriter for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+--- Services we know frontends for, by domain
+local DOMAINS = {
+	['youtube.com'] = 'youtube',
+	['www.youtube.com'] = 'youtube',
+	['m.youtube.com'] = 'youtube',
+	['youtu.be'] = 'youtube',
+	['reddit.com'] = 'reddit',
+	['www.reddit.com'] = 'reddit',
+	['old.reddit.com'] = 'reddit',
+	['new.reddit.com'] = 'reddit',
+	['np.reddit.com'] = 'reddit',
+}
+
+local Services = {}
+
+--- Invidious takes the same paths as YouTube
+---
+--- @param url Url
+--- @param rest string Everything after the domain
+--- @return string
+function Services.youtube(url, rest)
+	-- Short links only have the video ID as their path
+	if url:domain() == 'youtu.be' then
+		local id, params = rest:match('^/([^?#]*)%??(.*)$')
+		if not id then
+			return rest
+		elseif params ~= '' then
+			return '/watch?v=' .. id .. '&' .. params
+		end
+		return '/watch?v=' .. id
+	end
+
+	return rest
+end
+
+--- Redlib and Libreddit take the same paths as Reddit
+---
+--- @param _ Url
+--- @param rest string Everything after the domain
+--- @return string
+function Services.reddit(_, rest)
+	return rest
+end
+
+--- Rewrite links to the frontend instance the user picked for each service
+---
+--- Options map a service to the instance to use, services without one are
+--- left alone.
+add_postprocessor('frontends', function(results, options)
+	for _, res in ipairs(results) do
+		local url = Url.parse(res.url)
+		local service = DOMAINS[url:domain()]
+		local instance = service and options[service]
+
+		if instance then
+			local rest = res.url:match('^%a+://[^/?#]*(.*)$')
+			res.url = instance:gsub('/+$', '') .. Services[service](url, rest)
+			res.annotations[#res.annotations + 1] = 'Rewritten to ' .. Url.parse(instance):authority()
+		end
+	end
+
+	return results
+end)
@@ -11,6 +11,11 @@ add_ranker('multiprovider', function(results, options)
 				provider_weight = 1.1
 			end
 
+			-- Weights users gave providers
+			if options.weights ~= nil and options.weights[provider] ~= nil then
+				provider_weight = provider_weight * options.weights[provider]
+			end
+
 			if weights[i] == nil then
 				weights[i] = 1.0
 			else
@@ -29,6 +34,15 @@ local table_concat = function (dst, src)
 	 return dst
 end
 
+local merge_positions = function (dst, src)
+	for provider, pos in pairs(src) do
+		if dst[provider] == nil or pos < dst[provider] then
+			dst[provider] = pos
+		end
+	end
+	return dst
+end
+
 add_merger('multiprovider', function(results, options)
 	for i = 1, #results, 1 do
 		local max_weight = 1.1
@@ -39,10 +53,12 @@ add_merger('multiprovider', function(results, options)
 
 				if provider_weight ~= nil and provider_weight > max_weight then
 					results[j].providers = table_concat(results[j].providers, results[i].providers)
+					results[j].positions = merge_positions(results[j].positions, results[i].positions)
 					results[i] = results[j]
 				end
 
 				results[i].providers = table_concat(results[i].providers, results[j].providers)
+				results[i].positions = merge_positions(results[i].positions, results[j].positions)
 				results[j].providers = {}
 			end
 		end
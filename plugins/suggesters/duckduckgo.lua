@@ -0,0 +1,21 @@
+-- DuckDuckGo suggester for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+add_suggester('duckduckgo', function(client, query)
+	local url = Url.from_template('https://duckduckgo.com/ac/?q={query}&kl={kl}', {
+		query = query.query,
+		kl = duckduckgo_region(query),
+	}):string()
+
+	local json = parse_json(client:req('GET', url):headers({ ['Accept'] = 'application/json' }):send())
+
+	local suggestions = {}
+	for _, item in ipairs(json) do
+		if item.phrase then
+			table.insert(suggestions, item.phrase)
+		end
+	end
+
+	return suggestions
+end)
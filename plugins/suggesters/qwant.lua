@@ -0,0 +1,21 @@
+-- Qwant suggester for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+add_suggester('qwant', function(client, query)
+	local url = Url.from_template('https://api.qwant.com/v3/suggest?q={query}&locale={locale}&version=2', {
+		query = query.query,
+		locale = qwant_locale(query),
+	}):string()
+
+	local json = parse_json(client:req('GET', url):headers({ ['Accept'] = 'application/json' }):send())
+
+	local suggestions = {}
+	if json.status == 'success' and json.data and json.data.items then
+		for _, item in ipairs(json.data.items) do
+			table.insert(suggestions, item.value)
+		end
+	end
+
+	return suggestions
+end)
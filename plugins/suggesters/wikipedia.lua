@@ -0,0 +1,19 @@
+-- Wikipedia suggester for Searched
+-- Licensed MIT.
+-- (c) 2024 Dragynfruit
+
+add_suggester('wikipedia', function(client, query)
+	-- Article titles, from the Wikipedia in the user's language
+	local url = Url.from_template(
+		'https://{language}.wikipedia.org/w/api.php?action=opensearch&format=json&namespace=0&limit=10&search={query}',
+		{
+			query = query.query,
+			language = query.language or 'en',
+		}
+	):string()
+
+	local json = parse_json(client:req('GET', url):headers({ ['Accept'] = 'application/json' }):send())
+
+	-- The query, then the titles, then descriptions and links nobody fills in
+	return json[2] or {}
+end)
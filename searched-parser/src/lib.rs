@@ -1,5 +1,9 @@
 //extern crate combine;
 
+mod query;
+
+pub use query::{Query, Term, TermKind};
+
 use std::{collections::HashMap, ops::Range};
 
 //use combine::{between, choice, many, satisfy, token, Parser};
@@ -19,6 +23,10 @@ pub struct Url {
 }
 impl Url {
     pub fn parse(src: &[u8]) -> Self {
+        Self::try_parse(src).unwrap()
+    }
+    /// Parse a template, failing on unbalanced braces instead of panicking
+    pub fn try_parse(src: &[u8]) -> Result<Self, &'static str> {
         let mut elems = Vec::new();
 
         let len = src.len();
@@ -32,13 +40,14 @@ impl Url {
                 pos += 1;
             } else {
                 if src[pos] == b'{' {
-                    assert!(
-                        !unclosed,
-                        "can't open another dynamic before closing the last one"
-                    );
+                    if unclosed {
+                        return Err("can't open another dynamic before closing the last one");
+                    }
                     elems.push(UrlElem::Static(st..pos));
                 } else {
-                    assert!(unclosed, "can't close before opening a dynamic");
+                    if !unclosed {
+                        return Err("can't close before opening a dynamic");
+                    }
                     elems.push(UrlElem::Dynamic(st..pos));
                 }
 
@@ -48,38 +57,80 @@ impl Url {
                 pos = st;
             }
         }
+        if unclosed {
+            return Err("dynamic is never closed");
+        }
         if len > 0 && src[pos - 1] != b'}' {
             elems.push(UrlElem::Static(st..pos));
         }
+        if core::str::from_utf8(src).is_err() {
+            return Err("template isn't valid UTF-8");
+        }
 
-        Self {
+        Ok(Self {
             src: src.to_vec(),
             elems,
-        }
+        })
+    }
+    /// Names of the dynamics in the template
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.elems.iter().filter_map(|elem| match elem {
+            UrlElem::Dynamic(r) => core::str::from_utf8(self.src.get(r.clone()).unwrap()).ok(),
+            UrlElem::Static(_) => None,
+        })
     }
-    pub fn build(&self, values: HashMap<String, String>) -> String {
+    /// Fill in the template, failing on dynamics without a value
+    pub fn build(&self, values: HashMap<String, String>) -> Result<String, String> {
         self.elems
             .iter()
             .map(|elem| match elem {
                 UrlElem::Static(r) => {
-                    core::str::from_utf8(self.src.get(r.clone()).unwrap()).unwrap()
+                    Ok(core::str::from_utf8(self.src.get(r.clone()).unwrap()).unwrap())
+                }
+                UrlElem::Dynamic(r) => {
+                    let key = core::str::from_utf8(self.src.get(r.clone()).unwrap()).unwrap();
+                    values
+                        .get(key)
+                        .map(String::as_str)
+                        .ok_or_else(|| format!("no value for {{{key}}}"))
                 }
-                UrlElem::Dynamic(r) => values
-                    .get(core::str::from_utf8(self.src.get(r.clone()).unwrap()).unwrap())
-                    .unwrap(),
             })
-            .collect::<Vec<&str>>()
-            .concat()
-            .to_string()
+            .collect::<Result<Vec<&str>, String>>()
+            .map(|parts| parts.concat())
     }
 }
 
-//pub struct Query {
-//}
-//impl Query {
-//    pub fn parse() {
-//        many(choice([
-//            between(token('"'), token('"'), many(satisfy(|c| c != '"'))),
-//        ])).parse("").unwrap();
-//    }
-//}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn builds_templates() {
+        let url = Url::try_parse(b"https://example.com/{path}?q={query}").unwrap();
+        assert_eq!(url.keys().collect::<Vec<_>>(), ["path", "query"]);
+        assert_eq!(
+            url.build(values(&[("path", "search"), ("query", "rust")])).unwrap(),
+            "https://example.com/search?q=rust"
+        );
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces() {
+        assert!(Url::try_parse(b"https://example.com/{query").is_err());
+        assert!(Url::try_parse(b"https://example.com/query}").is_err());
+        assert!(Url::try_parse(b"https://example.com/{{query}}").is_err());
+    }
+
+    #[test]
+    fn missing_values_are_errors() {
+        let url = Url::try_parse(b"https://example.com/?q={query}").unwrap();
+        assert_eq!(url.build(HashMap::new()).unwrap_err(), "no value for {query}");
+    }
+}
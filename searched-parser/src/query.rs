@@ -0,0 +1,411 @@
+use core::fmt;
+
+/// A search query split into its terms and operators
+///
+/// Parsing never fails. Anything that isn't a well formed operator is kept
+/// as a plain word, and an unclosed quote runs to the end of the query.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Term {
+    /// Written with a leading `-`, results must not match it
+    pub negated: bool,
+    pub kind: TermKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TermKind {
+    Word(String),
+    /// `"exact words"`
+    Phrase(String),
+    /// `site:example.com`, lowercased
+    Site(String),
+    /// `filetype:pdf` or `ext:pdf`, lowercased and without a leading `.`
+    FileType(String),
+    /// `intitle:word` or `intitle:"some words"`
+    InTitle(String),
+    /// Terms joined by `OR` or `|`, any of them can match
+    Or(Vec<Term>),
+}
+
+impl Query {
+    pub fn parse(src: &str) -> Self {
+        let mut parser = Parser { src, pos: 0 };
+        let mut terms: Vec<Term> = Vec::new();
+        let mut or_pending = false;
+
+        while let Some(token) = parser.next_token() {
+            let term = match token {
+                Token::Or if !terms.is_empty() && !or_pending => {
+                    or_pending = true;
+                    continue;
+                }
+                Token::Or => Term {
+                    negated: false,
+                    kind: TermKind::Word(parser.last_raw().to_owned()),
+                },
+                Token::Term(term) => term,
+            };
+
+            if or_pending {
+                or_pending = false;
+                let last = terms.pop().unwrap();
+                let group = match last {
+                    Term {
+                        negated: false,
+                        kind: TermKind::Or(mut group),
+                    } => {
+                        group.push(term);
+                        group
+                    }
+                    last => vec![last, term],
+                };
+                terms.push(Term {
+                    negated: false,
+                    kind: TermKind::Or(group),
+                });
+            } else {
+                terms.push(term);
+            }
+        }
+        // A trailing OR joins nothing
+        if or_pending {
+            terms.push(Term {
+                negated: false,
+                kind: TermKind::Word("OR".to_owned()),
+            });
+        }
+
+        Self { terms }
+    }
+
+    /// The words and phrases to look for, without any operators
+    ///
+    /// For engines that don't understand any query syntax.
+    pub fn plain(&self) -> String {
+        let mut words = Vec::new();
+        collect_plain(&self.terms, &mut words);
+        words.join(" ")
+    }
+}
+
+fn collect_plain<'a>(terms: &'a [Term], words: &mut Vec<&'a str>) {
+    for term in terms.iter().filter(|term| !term.negated) {
+        match &term.kind {
+            TermKind::Word(text) | TermKind::Phrase(text) | TermKind::InTitle(text) => {
+                words.push(text)
+            }
+            TermKind::Or(group) => collect_plain(group, words),
+            TermKind::Site(_) | TermKind::FileType(_) => {}
+        }
+    }
+}
+
+/// Writes the query back in the common `site:`, `-term` and `"phrase"` syntax
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_terms(f, &self.terms, " ")
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negated {
+            f.write_str("-")?;
+        }
+        match &self.kind {
+            TermKind::Word(word) => f.write_str(word),
+            TermKind::Phrase(phrase) => write!(f, "\"{phrase}\""),
+            TermKind::Site(site) => write!(f, "site:{site}"),
+            TermKind::FileType(ext) => write!(f, "filetype:{ext}"),
+            TermKind::InTitle(text) if text.contains(char::is_whitespace) => {
+                write!(f, "intitle:\"{text}\"")
+            }
+            TermKind::InTitle(text) => write!(f, "intitle:{text}"),
+            TermKind::Or(group) => write_terms(f, group, " OR "),
+        }
+    }
+}
+
+fn write_terms(f: &mut fmt::Formatter<'_>, terms: &[Term], sep: &str) -> fmt::Result {
+    for (i, term) in terms.iter().enumerate() {
+        if i > 0 {
+            f.write_str(sep)?;
+        }
+        write!(f, "{term}")?;
+    }
+    Ok(())
+}
+
+enum Token {
+    Or,
+    Term(Term),
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    /// The token that was just read, as it was written
+    fn last_raw(&self) -> &'a str {
+        let end = self.pos;
+        let start = self.src[..end]
+            .rfind(char::is_whitespace)
+            .map_or(0, |i| i + 1);
+        &self.src[start..end]
+    }
+
+    /// Read up to the next whitespace
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    /// Read up to the closing quote, the opening one already read
+    fn quoted(&mut self) -> &'a str {
+        let rest = self.rest();
+        match rest.find('"') {
+            Some(len) => {
+                self.pos += len + 1;
+                &rest[..len]
+            }
+            None => {
+                self.pos = self.src.len();
+                rest
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.is_empty() {
+                return None;
+            }
+
+            let negated = trimmed.len() > 1
+                && trimmed.starts_with('-')
+                && !trimmed[1..].starts_with(char::is_whitespace);
+            if negated {
+                self.pos += 1;
+            }
+
+            if self.rest().starts_with('"') {
+                self.pos += 1;
+                let phrase = normalize_spaces(self.quoted());
+                // `""` has nothing to look for
+                if phrase.is_empty() {
+                    continue;
+                }
+                return Some(Token::Term(Term {
+                    negated,
+                    kind: TermKind::Phrase(phrase),
+                }));
+            }
+
+            let start = self.pos;
+            if let Some(kind) = self.operator() {
+                return Some(Token::Term(Term { negated, kind }));
+            }
+            self.pos = start;
+
+            let word = self.word();
+            if !negated && (word == "OR" || word == "|") {
+                return Some(Token::Or);
+            }
+            return Some(Token::Term(Term {
+                negated,
+                kind: TermKind::Word(word.to_owned()),
+            }));
+        }
+    }
+
+    /// Read a `name:value` operator, leaving `pos` anywhere if it isn't one
+    fn operator(&mut self) -> Option<TermKind> {
+        let rest = self.rest();
+        let colon = rest.find(':')?;
+        let name = &rest[..colon];
+        if name.contains(char::is_whitespace) {
+            return None;
+        }
+        let make: fn(String) -> TermKind = match name.to_ascii_lowercase().as_str() {
+            "site" => TermKind::Site,
+            "filetype" | "ext" => TermKind::FileType,
+            "intitle" => TermKind::InTitle,
+            _ => return None,
+        };
+
+        self.pos += colon + 1;
+        let value = if self.rest().starts_with('"') {
+            self.pos += 1;
+            normalize_spaces(self.quoted())
+        } else {
+            self.word().to_owned()
+        };
+        let kind = match make(value) {
+            TermKind::Site(site) => TermKind::Site(site.to_lowercase()),
+            TermKind::FileType(ext) => {
+                TermKind::FileType(ext.trim_start_matches('.').to_lowercase())
+            }
+            kind => kind,
+        };
+        match &kind {
+            TermKind::Site(value) | TermKind::FileType(value) | TermKind::InTitle(value)
+                if value.is_empty() =>
+            {
+                None
+            }
+            _ => Some(kind),
+        }
+    }
+}
+
+fn normalize_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Term {
+        Term {
+            negated: false,
+            kind: TermKind::Word(text.to_owned()),
+        }
+    }
+
+    fn not(term: Term) -> Term {
+        Term {
+            negated: true,
+            ..term
+        }
+    }
+
+    fn kind(kind: TermKind) -> Term {
+        Term {
+            negated: false,
+            kind,
+        }
+    }
+
+    fn terms(src: &str) -> Vec<Term> {
+        Query::parse(src).terms
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(terms("  rust   lang "), [word("rust"), word("lang")]);
+        assert_eq!(terms(""), []);
+    }
+
+    #[test]
+    fn phrases() {
+        assert_eq!(
+            terms(r#"say "hello   world" "#),
+            [word("say"), kind(TermKind::Phrase("hello world".to_owned()))]
+        );
+        // Unclosed quotes run to the end, empty ones are dropped
+        assert_eq!(terms(r#""a b"#), [kind(TermKind::Phrase("a b".to_owned()))]);
+        assert_eq!(terms(r#"a "" b"#), [word("a"), word("b")]);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            terms(r#"Site:Example.COM filetype:.PDF ext:txt intitle:"two  words" intitle:one"#),
+            [
+                kind(TermKind::Site("example.com".to_owned())),
+                kind(TermKind::FileType("pdf".to_owned())),
+                kind(TermKind::FileType("txt".to_owned())),
+                kind(TermKind::InTitle("two words".to_owned())),
+                kind(TermKind::InTitle("one".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_operators_are_words() {
+        assert_eq!(terms("site: unknown:x"), [word("site:"), word("unknown:x")]);
+        assert_eq!(terms("http://example.com"), [word("http://example.com")]);
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(
+            terms(r#"-spam -"junk mail" -site:example.com - x"#),
+            [
+                not(word("spam")),
+                not(kind(TermKind::Phrase("junk mail".to_owned()))),
+                not(kind(TermKind::Site("example.com".to_owned()))),
+                word("-"),
+                word("x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn or_groups() {
+        assert_eq!(
+            terms("a OR b | c d"),
+            [kind(TermKind::Or(vec![word("a"), word("b"), word("c")])), word("d")]
+        );
+        assert_eq!(
+            terms("-a OR b"),
+            [kind(TermKind::Or(vec![not(word("a")), word("b")]))]
+        );
+    }
+
+    #[test]
+    fn stray_ors_are_words() {
+        assert_eq!(terms("OR a"), [word("OR"), word("a")]);
+        assert_eq!(terms("a OR"), [word("a"), word("OR")]);
+        assert_eq!(
+            terms("a OR OR b"),
+            [kind(TermKind::Or(vec![word("a"), word("OR")])), word("b")]
+        );
+        assert_eq!(terms("-OR"), [not(word("OR"))]);
+    }
+
+    #[test]
+    fn plain() {
+        let query = Query::parse(r#"rust -java site:example.com "async io" intitle:guide a OR b"#);
+        assert_eq!(query.plain(), "rust async io guide a b");
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for src in [
+            "rust lang",
+            r#""async io" -java"#,
+            "site:example.com filetype:pdf",
+            r#"intitle:"two words" intitle:one"#,
+            r#"a OR -b OR "c d""#,
+            "-site:example.com/blog x",
+        ] {
+            let query = Query::parse(src);
+            assert_eq!(query.to_string(), src);
+            assert_eq!(Query::parse(&query.to_string()), query);
+        }
+    }
+
+    #[test]
+    fn display_normalizes() {
+        assert_eq!(
+            Query::parse(r#"ext:.PDF  a | b "x  y"#).to_string(),
+            r#"filetype:pdf a OR b "x y""#
+        );
+    }
+}
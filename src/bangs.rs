@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use url::Url;
+
+/// What a bang does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BangTarget {
+    /// Send the user to this URL template instead of searching
+    Redirect(String),
+    /// Only ask this provider
+    Provider(String),
+}
+
+/// Split the first `!bang` that `resolve` knows off a query
+///
+/// Words like `!important` that aren't bangs stay in the query. Returns the
+/// lowercased bang without its `!`, what it resolved to and the rest of the
+/// query.
+pub fn split<T>(query: &str, resolve: impl Fn(&str) -> Option<T>) -> Option<(String, T, String)> {
+    let words = query.split_whitespace().collect::<Vec<_>>();
+    let (i, trigger, target) = words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| word.len() > 1 && word.starts_with('!'))
+        .find_map(|(i, word)| {
+            let trigger = word[1..].to_lowercase();
+            resolve(&trigger).map(|target| (i, trigger, target))
+        })?;
+
+    let rest = words
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| *j != i)
+        .map(|(_, word)| *word)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some((trigger, target, rest))
+}
+
+/// Build the URL a redirect bang sends the user to
+///
+/// Templates can only use `{query}`, and have to make an `http` or `https`
+/// URL. Anything else gives `None`.
+pub fn fill(template: &str, query: &str) -> Option<String> {
+    let template = searched_parser::Url::try_parse(template.as_bytes()).ok()?;
+    if template.keys().any(|key| key != "query") {
+        return None;
+    }
+
+    let url = template.build(HashMap::from([(
+        "query".to_owned(),
+        urlencoding::encode(query).into_owned(),
+    )]))
+    .ok()?;
+    Url::parse(&url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(String::from)
+}
+
+/// Whether a URL template can be used for a redirect bang
+pub fn is_valid_template(template: &str) -> bool {
+    fill(template, "").is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(bang: &str) -> Option<&'static str> {
+        match bang {
+            "mdn" => Some("mdn"),
+            "w" => Some("wikipedia"),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn finds_bangs_anywhere() {
+        assert_eq!(
+            split("rust !W ownership", known),
+            Some(("w".to_owned(), "wikipedia", "rust ownership".to_owned()))
+        );
+    }
+
+    #[test]
+    fn skips_words_that_arent_bangs() {
+        assert_eq!(
+            split("!important css !mdn", known),
+            Some(("mdn".to_owned(), "mdn", "!important css".to_owned()))
+        );
+    }
+
+    #[test]
+    fn ignores_lone_exclamation_marks() {
+        assert_eq!(split("wow !", |_| Some(())), None);
+    }
+
+    #[test]
+    fn unknown_bangs_leave_the_query_alone() {
+        assert_eq!(split("!nope rust", known), None);
+    }
+
+    #[test]
+    fn fills_templates() {
+        assert_eq!(
+            fill("https://example.com/?q={query}", "a b&c").as_deref(),
+            Some("https://example.com/?q=a%20b%26c")
+        );
+    }
+
+    #[test]
+    fn rejects_unusable_templates() {
+        assert_eq!(fill("javascript:alert({query})", "x"), None);
+        assert_eq!(fill("https://example.com/{lang}?q={query}", "x"), None);
+        assert_eq!(fill("https://example.com/?q={query", "x"), None);
+        assert!(!is_valid_template("ftp://example.com/{query}"));
+    }
+}
@@ -0,0 +1,245 @@
+use std::{error::Error, path::Path, process::ExitCode, time::Instant};
+
+use clap::{Args, Parser, Subcommand};
+use reqwest::Client;
+use searched::{
+    Kind, Query, SafeSearch, SearchOptions, SearchResponse, TimeRange, config::Config,
+    lua_support::PluginEngine,
+};
+
+/// A privacy-respecting metasearch engine
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the web server, the default
+    Serve {
+        /// Address to listen on, overrides `listen_addr` in config.toml
+        #[arg(long)]
+        listen: Option<String>,
+    },
+    /// Search like the web interface would and print the results
+    Search(SearchArgs),
+    /// Search a single provider and report how it went
+    EngineTest {
+        /// Provider to search, as named in providers.toml
+        provider: String,
+        /// What to search for
+        #[arg(default_value = "searched")]
+        query: String,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Work with the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Check config.toml, providers.toml, bangs.toml and the plugins for problems
+    Check,
+}
+
+#[derive(Args)]
+pub struct SearchArgs {
+    /// What to search for, operators and bangs included
+    #[arg(required = true)]
+    query: Vec<String>,
+    /// Kind of results: sear, imgs, vids, news, maps, wiki, qans, docs or pprs
+    #[arg(short, long, default_value = "sear", value_parser = parse_kind)]
+    kind: Kind,
+    /// Page of results, starting from 1
+    #[arg(short, long, default_value_t = 1)]
+    page: usize,
+    /// Safe search: off, moderate or strict
+    #[arg(short, long, default_value = "moderate", value_parser = parse_with::<SafeSearch>)]
+    safe: SafeSearch,
+    /// Only show results this recent: day, week, month or year
+    #[arg(short, long, value_parser = parse_with::<TimeRange>)]
+    time_range: Option<TimeRange>,
+    /// Preferred language of results, as an ISO 639-1 code
+    #[arg(long)]
+    lang: Option<String>,
+    /// Preferred region of results, as an ISO 3166-1 code
+    #[arg(long)]
+    region: Option<String>,
+    /// Search only these providers, can be repeated
+    #[arg(long = "provider")]
+    providers: Vec<String>,
+    /// Print the whole response as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+fn parse_kind(s: &str) -> Result<Kind, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_owned()))
+        .map_err(|_| format!("unknown kind {s:?}"))
+}
+
+fn parse_with<T: std::str::FromStr>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("unknown value {s:?}"))
+}
+
+/// A plugin engine for one command, caching into a throwaway database
+///
+/// The server's database is locked while it runs, and searches from here
+/// shouldn't go stale anyway.
+async fn engine(client: Client, config: &Config) -> Result<PluginEngine, Box<dyn Error>> {
+    let db = sled::Config::new().temporary(true).open()?;
+    PluginEngine::new(client, config, &db).await
+}
+
+pub async fn search(args: SearchArgs, client: Client, config: &Config) -> Result<ExitCode, Box<dyn Error>> {
+    let eng = engine(client, config).await?;
+
+    let query = Query {
+        query: args.query.join(" "),
+        kind: args.kind,
+        page: args.page,
+        safe: args.safe,
+        no_cache: true,
+        language: args.lang,
+        region: args.region.map(|region| region.to_uppercase()),
+        time_range: args.time_range,
+    };
+    let options = SearchOptions::default();
+    let providers = if args.providers.is_empty() {
+        eng.providers_for(query.kind, &options)
+    } else {
+        args.providers
+    };
+
+    let response = eng.search(query, providers, &options).await?;
+    let found = !response.results.is_empty() || response.redirect.is_some();
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+    } else {
+        print_response(&response);
+    }
+
+    Ok(if found { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+fn print_response(response: &SearchResponse) {
+    if let Some(redirect) = &response.redirect {
+        println!("redirect: {redirect}");
+        return;
+    }
+
+    if let Some(correction) = response.corrections.first() {
+        println!("did you mean: {correction}");
+    }
+
+    for (i, result) in response.results.iter().enumerate() {
+        println!(
+            "{:>3}  {}\n     {}\n     {}",
+            i + 1,
+            clean_title(&result.title),
+            result.url,
+            result.providers.join(", ")
+        );
+    }
+
+    for err in &response.errors {
+        eprintln!("{} failed: {}", err.provider, err.error);
+    }
+    for warning in &response.warnings {
+        eprintln!("{warning}");
+    }
+    if !response.suspended.is_empty() {
+        eprintln!("suspended: {}", response.suspended.join(", "));
+    }
+    eprintln!("{} results", response.results.len());
+}
+
+/// Titles as the user would read them, engines can leave markup and entities in
+fn clean_title(title: &str) -> String {
+    let mut text = String::with_capacity(title.len());
+    let mut in_tag = false;
+    for c in title.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    html_entities::decode_html_entities(&text).unwrap_or(text)
+}
+
+pub async fn engine_test(
+    provider: String,
+    query: String,
+    json: bool,
+    client: Client,
+    config: &Config,
+) -> Result<ExitCode, Box<dyn Error>> {
+    let eng = engine(client, config).await?;
+    if !eng.providers().0.contains_key(&provider) {
+        eprintln!("no provider named {provider}");
+        return Ok(ExitCode::FAILURE);
+    }
+
+    let query = Query {
+        query,
+        page: 1,
+        no_cache: true,
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let result = eng.search_provider(query, &provider).await;
+    let took = start.elapsed().as_millis();
+
+    match result {
+        Ok(response) => {
+            if json {
+                println!("{}", serde_json::to_string_pretty(&response)?);
+            }
+            eprintln!("{provider}: {} results in {took}ms", response.results.len());
+            if let Some(correction) = &response.correction {
+                eprintln!("{provider}: corrected to {correction:?}");
+            }
+            Ok(if response.results.is_empty() { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+        }
+        Err(err) => {
+            eprintln!("{provider}: failed after {took}ms: {err}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+pub async fn config_check() -> ExitCode {
+    let config = if Path::new("config.toml").exists() {
+        match Config::try_load("config.toml") {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("config.toml can't be loaded: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        Config::default()
+    };
+
+    let problems = PluginEngine::check(&config).await;
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+
+    if problems.is_empty() {
+        eprintln!("no problems found");
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("{} problems found", problems.len());
+        ExitCode::FAILURE
+    }
+}
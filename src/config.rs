@@ -12,7 +12,7 @@ macro_rules! gen_enum {
         }
     )* ) => {
         $(
-        #[derive(Deserialize, Serialize, PartialEq, Clone, Copy, Debug)]
+        #[derive(Deserialize, Serialize, PartialEq, Eq, Hash, Clone, Copy, Debug)]
         pub enum $ident {
             $(
             #[serde(rename = $string)]
@@ -34,12 +34,239 @@ macro_rules! gen_enum {
 pub struct Config {
     /// Address to listen on
     pub listen_addr: Option<String>,
+    /// Search dispatch options
+    #[serde(default)]
+    pub search: CfgSearch,
+    /// Plugin runtime options
+    #[serde(default)]
+    pub plugins: CfgPlugins,
+    /// Which merger and ranker searches use
+    #[serde(default)]
+    pub ranking: CfgRanking,
+    /// Autocomplete options
+    #[serde(default)]
+    pub suggest: CfgSuggest,
 }
 impl Config {
     pub fn load(path: impl AsRef<Path>) -> Self {
+        Self::try_load(path).unwrap()
+    }
+
+    /// Load the config, failing on a missing or invalid file
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let mut buf = String::new();
-        File::open(path).unwrap().read_to_string(&mut buf).unwrap();
-        toml::from_str(&buf).unwrap()
+        File::open(path)?.read_to_string(&mut buf)?;
+        Ok(toml::from_str(&buf)?)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct CfgSearch {
+    /// Time a single provider gets to answer, in milliseconds
+    ///
+    /// Can be overridden per provider with `timeout` in providers.toml
+    pub provider_timeout: u64,
+    /// Time the whole search gets before whatever has answered is
+    /// merged and ranked, in milliseconds
+    pub deadline: u64,
+    /// How long provider responses are cached, in seconds
+    ///
+    /// Can be overridden per provider with `cache_ttl` in providers.toml,
+    /// 0 disables caching
+    pub cache_ttl: u64,
+    /// Consecutive failures before a provider is suspended
+    pub failure_threshold: u32,
+    /// How long a provider is first suspended for, in seconds
+    ///
+    /// Doubles with every failure after that
+    pub backoff_base: u64,
+    /// Longest a provider can be suspended for, in seconds
+    pub backoff_max: u64,
+    /// Providers asked for each kind
+    ///
+    /// Kinds left out ask every provider that lists them in providers.toml
+    pub providers: HashMap<Kind, Vec<String>>,
+}
+impl Default for CfgSearch {
+    fn default() -> Self {
+        Self {
+            provider_timeout: 3000,
+            deadline: 5000,
+            cache_ttl: 600,
+            failure_threshold: 3,
+            backoff_base: 30,
+            backoff_max: 1800,
+            providers: HashMap::from([(
+                Kind::General,
+                ["duckduckgo", "stract", "qwant", "mojeek", "ask"]
+                    .map(String::from)
+                    .to_vec(),
+            )]),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct CfgSuggest {
+    /// Suggesters asked for completions, in order of preference
+    ///
+    /// Every registered suggester is asked if unset
+    pub suggesters: Option<Vec<String>>,
+    /// Time suggesters get to answer, in milliseconds
+    ///
+    /// Completions have to keep up with typing, so this is kept short
+    pub timeout: u64,
+    /// How long completions are cached, in seconds, 0 disables caching
+    pub cache_ttl: u64,
+    /// Most prefixes whose completions are cached, in memory
+    pub cache_size: usize,
+    /// Most completions returned
+    pub limit: usize,
+}
+impl Default for CfgSuggest {
+    fn default() -> Self {
+        Self {
+            suggesters: None,
+            timeout: 800,
+            cache_ttl: 3600,
+            cache_size: 10000,
+            limit: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct CfgPlugins {
+    /// Number of Lua states plugins run on
+    ///
+    /// Every plugin call takes a state for itself, including while it waits
+    /// on requests. Uses four per CPU core if unset.
+    pub pool_size: Option<usize>,
+    /// Time a single plugin call may spend running, in milliseconds
+    ///
+    /// Time spent waiting on requests doesn't count, 0 disables the limit
+    pub call_time_limit: u64,
+    /// Memory each Lua state may use, in megabytes
+    ///
+    /// 0 disables the limit
+    pub memory_limit: usize,
+    /// Keep `os`, `io`, `package` and file loading out of plugins' reach
+    pub sandbox: bool,
+}
+impl Default for CfgPlugins {
+    fn default() -> Self {
+        Self {
+            pool_size: None,
+            call_time_limit: 1000,
+            memory_limit: 64,
+            sandbox: true,
+        }
+    }
+}
+impl CfgPlugins {
+    pub fn pool_size(&self) -> usize {
+        self.pool_size
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()) * 4)
+            .max(1)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct CfgRanking {
+    /// Merger used for kinds without their own
+    pub merger: String,
+    /// Ranker used for kinds without their own
+    pub ranker: String,
+    /// Options passed to the default merger
+    pub merger_options: HashMap<String, toml::Value>,
+    /// Options passed to the default ranker
+    pub ranker_options: HashMap<String, toml::Value>,
+    /// Overrides for single kinds
+    pub kinds: HashMap<Kind, CfgKindRanking>,
+}
+impl Default for CfgRanking {
+    fn default() -> Self {
+        Self {
+            merger: "canonical".to_owned(),
+            ranker: "rrf".to_owned(),
+            merger_options: HashMap::new(),
+            ranker_options: HashMap::new(),
+            kinds: HashMap::new(),
+        }
+    }
+}
+impl CfgRanking {
+    /// Get the merger for a kind
+    pub fn merger(&self, kind: Kind) -> CfgStage {
+        let cfg = self.kinds.get(&kind);
+        Self::stage(
+            cfg.and_then(|k| k.merger.as_ref()),
+            cfg.and_then(|k| k.merger_options.as_ref()),
+            &self.merger,
+            &self.merger_options,
+        )
+    }
+
+    /// Get the ranker for a kind
+    pub fn ranker(&self, kind: Kind) -> CfgStage {
+        let cfg = self.kinds.get(&kind);
+        Self::stage(
+            cfg.and_then(|k| k.ranker.as_ref()),
+            cfg.and_then(|k| k.ranker_options.as_ref()),
+            &self.ranker,
+            &self.ranker_options,
+        )
+    }
+
+    fn stage(
+        name: Option<&String>,
+        options: Option<&HashMap<String, toml::Value>>,
+        default_name: &String,
+        default_options: &HashMap<String, toml::Value>,
+    ) -> CfgStage {
+        // The default options only make sense for the default stage
+        let default_options = if name.is_none() {
+            default_options.clone()
+        } else {
+            HashMap::new()
+        };
+
+        CfgStage {
+            name: name.unwrap_or(default_name).clone(),
+            options: options.cloned().unwrap_or(default_options),
+        }
+    }
+}
+
+/// Merger and ranker overrides for a kind, unset fields use the defaults
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct CfgKindRanking {
+    pub merger: Option<String>,
+    pub ranker: Option<String>,
+    pub merger_options: Option<HashMap<String, toml::Value>>,
+    pub ranker_options: Option<HashMap<String, toml::Value>>,
+}
+
+/// A merger or ranker, along with the options it gets
+#[derive(Default, Clone, Debug)]
+pub struct CfgStage {
+    pub name: String,
+    pub options: HashMap<String, toml::Value>,
+}
+impl CfgStage {
+    /// Use another merger or ranker by name, without options unless it's the same one
+    pub fn with_name(self, name: Option<&String>) -> Self {
+        match name {
+            Some(name) if *name != self.name => Self {
+                name: name.clone(),
+                options: HashMap::new(),
+            },
+            _ => self,
+        }
     }
 }
 
@@ -47,9 +274,26 @@ impl Config {
 pub struct ProvidersConfig(pub HashMap<String, CfgProvider>);
 impl ProvidersConfig {
     pub fn load(path: impl AsRef<Path>) -> Self {
+        Self::try_load(path).unwrap()
+    }
+
+    /// Load the provider config, failing on a missing or invalid file
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let mut buf = String::new();
-        File::open(path).unwrap().read_to_string(&mut buf).unwrap();
-        toml::from_str(&buf).unwrap()
+        File::open(path)?.read_to_string(&mut buf)?;
+        Ok(toml::from_str(&buf)?)
+    }
+
+    /// Names of every provider that searches a kind, sorted
+    pub fn for_kind(&self, kind: Kind) -> Vec<String> {
+        let mut providers = self
+            .0
+            .iter()
+            .filter(|(_, provider)| provider.kinds.contains(&kind))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        providers.sort_unstable();
+        providers
     }
 }
 
@@ -64,8 +308,60 @@ pub struct CfgProvider {
     pub description: String,
     pub kinds: Vec<Kind>,
     pub features: Option<CfgProviderFeatures>,
+    /// Time this provider gets to answer, in milliseconds
+    ///
+    /// Uses `search.provider_timeout` if unset
+    pub timeout: Option<u64>,
+    /// How long this provider's responses are cached, in seconds
+    ///
+    /// Uses `search.cache_ttl` if unset
+    pub cache_ttl: Option<u64>,
     /// Extra engine-specific options
     pub extra: Option<HashMap<String, toml::Value>>,
+    /// Options from `extra` users can change on the settings page
+    #[serde(default)]
+    pub settings: HashMap<String, CfgProviderSetting>,
+}
+
+/// Bangs every user has, by what's typed after the `!`
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct BangsConfig(pub HashMap<String, CfgBang>);
+impl BangsConfig {
+    /// Load the bang config, failing on a missing or invalid file
+    pub fn try_load(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut buf = String::new();
+        File::open(path)?.read_to_string(&mut buf)?;
+        Ok(toml::from_str(&buf)?)
+    }
+}
+
+/// A `!bang` shortcut
+///
+/// Either `url` or `provider` should be set.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct CfgBang {
+    /// Human readable name
+    pub name: String,
+    /// Send the user here, with `{query}` replaced by the rest of the query
+    pub url: Option<String>,
+    /// Only ask this provider
+    pub provider: Option<String>,
+}
+
+/// An engine option users can change
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+pub struct CfgProviderSetting {
+    /// Label shown on the settings page
+    pub name: String,
+    /// Values users can pick from, any value is allowed if empty
+    #[serde(default)]
+    pub choices: Vec<String>,
+}
+impl CfgProviderSetting {
+    /// Whether users may set the option to this value
+    pub fn allows(&self, value: &str) -> bool {
+        self.choices.is_empty() || self.choices.iter().any(|choice| choice == value)
+    }
 }
 
 gen_enum! {
@@ -1,4 +1,81 @@
-#[derive(Debug)]
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "detail")]
 pub enum Error {
-    EngineNotLoaded,
+    /// No provider with this name in providers.toml
+    ProviderNotFound(String),
+    /// The engine a provider uses was never registered with `add_engine`
+    EngineNotLoaded(String),
+    /// No merger registered under this name
+    MergerNotFound(String),
+    /// No ranker registered under this name
+    RankerNotFound(String),
+    /// No suggester registered under this name
+    SuggesterNotFound(String),
+    /// A plugin raised an error or misbehaved
+    Lua(String),
+    /// A request to an upstream failed
+    Http(String),
+    /// An upstream response or plugin result couldn't be understood
+    Parse(String),
+    /// The provider didn't answer in time
+    Timeout,
+    /// A plugin ran out of time or memory
+    LimitExceeded(String),
+    /// A recorded response is missing or couldn't be saved
+    Fixture(String),
+    /// The plugin at this path failed to load
+    PluginLoad(String, Box<Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ProviderNotFound(name) => write!(f, "no provider named {name}"),
+            Error::EngineNotLoaded(name) => write!(f, "engine {name} isn't loaded"),
+            Error::MergerNotFound(name) => write!(f, "no merger named {name}"),
+            Error::RankerNotFound(name) => write!(f, "no ranker named {name}"),
+            Error::SuggesterNotFound(name) => write!(f, "no suggester named {name}"),
+            Error::Lua(msg) => write!(f, "plugin error: {msg}"),
+            Error::Http(msg) => write!(f, "request failed: {msg}"),
+            Error::Parse(msg) => write!(f, "couldn't parse response: {msg}"),
+            Error::Timeout => write!(f, "timed out"),
+            Error::LimitExceeded(limit) => write!(f, "plugin exceeded its {limit}"),
+            Error::Fixture(msg) => write!(f, "fixture error: {msg}"),
+            Error::PluginLoad(path, err) => write!(f, "couldn't load plugin {path}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<mlua::Error> for Error {
+    fn from(err: mlua::Error) -> Self {
+        // Errors raised by our own Lua API keep their kind
+        let mut cause = &err;
+        loop {
+            match cause {
+                mlua::Error::CallbackError { cause: inner, .. }
+                | mlua::Error::WithContext { cause: inner, .. } => cause = inner,
+                mlua::Error::ExternalError(inner) => {
+                    if let Some(inner) = inner.downcast_ref::<Error>() {
+                        return inner.clone();
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+
+        // Only keep the message, the traceback isn't useful to users
+        let msg = err.to_string().lines().next().unwrap_or_default().to_owned();
+        match cause {
+            mlua::Error::FromLuaConversionError { .. } | mlua::Error::DeserializeError(_) => {
+                Error::Parse(msg)
+            }
+            mlua::Error::MemoryError(_) => Error::LimitExceeded("memory limit".to_owned()),
+            _ => Error::Lua(msg),
+        }
+    }
 }
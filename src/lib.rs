@@ -6,6 +6,7 @@ extern crate tokio;
 extern crate serde;
 extern crate searched_parser;
 
+pub mod bangs;
 pub mod config;
 mod error;
 pub mod lua_support;
@@ -14,23 +15,12 @@ pub mod settings;
 pub use error::Error;
 use url::Url;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
-use config::ProvidersConfig;
 use mlua::{FromLua, IntoLua, LuaSerdeExt};
-use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
-pub static PROVIDER_KINDS: Lazy<HashMap<String, Vec<Kind>>> = Lazy::new(|| {
-    HashMap::from_iter(
-        ProvidersConfig::load("plugins/providers.toml")
-            .0
-            .into_iter()
-            .map(|(k, v)| (k, v.kinds)),
-    )
-});
-
 gen_enum! {
     Kind (Kind::General) {
         General        = "sear",
@@ -49,6 +39,13 @@ gen_enum! {
         Moderate = "moderate",
         Strict   = "strict",
     }
+
+    TimeRange {
+        Day   = "day",
+        Week  = "week",
+        Month = "month",
+        Year  = "year",
+    }
 }
 
 impl IntoLua for Kind {
@@ -84,6 +81,42 @@ impl std::fmt::Display for SafeSearch {
     }
 }
 
+impl FromStr for TimeRange {
+    type Err = ();
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "day" => Ok(TimeRange::Day),
+            "week" => Ok(TimeRange::Week),
+            "month" => Ok(TimeRange::Month),
+            "year" => Ok(TimeRange::Year),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeRange::Day => write!(f, "day"),
+            TimeRange::Week => write!(f, "week"),
+            TimeRange::Month => write!(f, "month"),
+            TimeRange::Year => write!(f, "year"),
+        }
+    }
+}
+
+impl TimeRange {
+    /// How far back results in the range can go, a bit generous for months and years
+    pub fn duration(self) -> chrono::TimeDelta {
+        chrono::TimeDelta::days(match self {
+            TimeRange::Day => 1,
+            TimeRange::Week => 7,
+            TimeRange::Month => 31,
+            TimeRange::Year => 366,
+        })
+    }
+}
+
 #[derive(Debug, Default, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Query {
     #[serde(rename(deserialize = "q"))]
@@ -94,6 +127,135 @@ pub struct Query {
     pub page: usize,
     #[serde(rename(deserialize = "s"), default)]
     pub safe: SafeSearch,
+    /// Skip cached provider responses
+    #[serde(rename(deserialize = "nocache"), default)]
+    pub no_cache: bool,
+    /// Preferred language of results, as a lowercase ISO 639-1 code
+    #[serde(rename(deserialize = "lang"), default)]
+    pub language: Option<String>,
+    /// Preferred region of results, as an uppercase ISO 3166-1 code
+    #[serde(default)]
+    pub region: Option<String>,
+    /// Only show results from this far back
+    #[serde(rename(deserialize = "t"), default)]
+    pub time_range: Option<TimeRange>,
+}
+
+impl Query {
+    /// `Accept-Language` for requests made for this query, if it has a language
+    pub fn accept_language(&self) -> Option<String> {
+        let language = self.language.as_deref()?;
+        let mut accept = match &self.region {
+            Some(region) => format!("{language}-{region},{language};q=0.9"),
+            None => language.to_owned(),
+        };
+        // Pages in English beat error pages
+        if language != "en" {
+            accept.push_str(",en;q=0.5");
+        }
+        Some(accept)
+    }
+}
+
+/// Per-request search options that aren't part of the query
+#[derive(Debug, Default, Clone)]
+pub struct SearchOptions {
+    /// Options for each postprocessor, by name
+    pub postprocessors: HashMap<String, HashMap<String, String>>,
+    /// Use this merger instead of the configured one
+    pub merger: Option<String>,
+    /// Use this ranker instead of the configured one
+    pub ranker: Option<String>,
+    /// The user's choices for each provider, by name
+    pub providers: HashMap<String, ProviderOptions>,
+    /// The user's own redirect bangs, URL templates by bang
+    pub bangs: HashMap<String, String>,
+}
+
+/// A user's choices for one provider
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderOptions {
+    /// Ask or skip this provider, unset keeps the instance's choice
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Multiplies the provider's weight when ranking
+    #[serde(default)]
+    pub weight: Option<f64>,
+    /// Engine options to use instead of the ones in providers.toml
+    ///
+    /// Only the ones the provider lists under `settings` are used.
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Ranked results of a search, along with the providers that didn't make it
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SearchResponse {
+    /// The query that was searched, after preprocessing
+    pub query: Query,
+    /// Where to send the user instead, if a bang or preprocessor asked for it
+    pub redirect: Option<String>,
+    /// The bang that limited the search to one provider, without its `!`
+    pub bang: Option<String>,
+    pub results: Vec<SearchResult>,
+    /// Spelling corrections providers suggested, the most agreed on first
+    pub corrections: Vec<String>,
+    /// Other searches providers suggested
+    pub related: Vec<String>,
+    /// The most results any provider estimated having
+    pub total: Option<u64>,
+    /// Providers that were asked, in order
+    pub queried: Vec<String>,
+    /// Milliseconds each provider that answered took, whether it failed or not
+    pub timings: HashMap<String, u64>,
+    /// Providers that failed or didn't answer in time
+    pub errors: Vec<ProviderError>,
+    /// Providers skipped because they have been failing
+    pub suspended: Vec<String>,
+    /// Providers that haven't answered yet, only set on streamed updates
+    pub pending: Vec<String>,
+    /// Mergers and rankers that failed, the native ones were used instead
+    pub warnings: Vec<String>,
+}
+
+/// Everything a provider answered to a search
+///
+/// Engines return either just their results, or a table with the results
+/// under `results` and any of the other fields next to them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderResponse {
+    pub results: Vec<SearchResult>,
+    /// The query the provider thinks was meant
+    #[serde(default)]
+    pub correction: Option<String>,
+    /// Other searches the provider suggests
+    #[serde(default)]
+    pub related: Vec<String>,
+    /// How many results the provider estimates it has
+    #[serde(default)]
+    pub total: Option<u64>,
+}
+
+/// A search that sends its results as providers answer
+///
+/// Every update has all results so far, merged and ranked again. The last
+/// one has every provider that answered in time, after which the channel
+/// closes. No updates are sent if a preprocessor redirected the search.
+pub struct SearchStream {
+    /// The query that is being searched, after preprocessing
+    pub query: Query,
+    /// Where to send the user instead, if a bang or preprocessor asked for it
+    pub redirect: Option<String>,
+    /// The bang that limited the search to one provider, without its `!`
+    pub bang: Option<String>,
+    pub updates: tokio::sync::mpsc::Receiver<Result<SearchResponse, Error>>,
+}
+
+/// Why a provider has no results
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderError {
+    pub provider: String,
+    pub error: Error,
 }
 
 #[derive(Hash, PartialEq, Eq, Debug, Clone, Deserialize, Serialize)]
@@ -105,6 +267,17 @@ pub struct SearchResult {
     pub general: Option<GeneralResult>,
     pub forum: Option<ForumResult>,
     pub image: Option<ImageResult>,
+    /// When the page was published, as RFC 3339
+    ///
+    /// Engines can also give `YYYY-MM-DD` or Unix seconds.
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Position each provider gave the result, starting from 1
+    #[serde(default)]
+    pub positions: BTreeMap<String, usize>,
+    /// Notes added by postprocessors
+    #[serde(default)]
+    pub annotations: Vec<String>,
 }
 
 #[derive(Hash, PartialEq, Eq, Debug, Default, Clone, Deserialize, Serialize)]
@@ -123,3 +296,35 @@ pub struct ImageResult {
     pub preview_url: String,
     pub full_size_url: String,
 }
+
+/// The client engines search with, looking like a regular browser
+///
+/// Shared with the engine tests, so recorded fixtures match what the server
+/// would have been sent.
+pub fn http_client() -> reqwest::Result<reqwest::Client> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (key, val) in [
+        (
+            "User-Agent",
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:129.0) Gecko/20100101 Firefox/129.0",
+        ),
+        (
+            "Accept",
+            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
+        ),
+        ("Accept-Language", "en-US,en;q=0.5"),
+        ("Accept-Encoding", "gzip"),
+        ("DNT", "1"),
+        ("Connection", "keep-alive"),
+        ("Upgrade-Insecure-Requests", "1"),
+        ("Sec-Fetch-Dest", "document"),
+        ("Sec-Fetch-Mode", "navigate"),
+        ("Sec-Fetch-Site", "none"),
+        ("Sec-Fetch-User", "?1"),
+        ("Priority", "u=1"),
+        ("TE", "trailers"),
+    ] {
+        headers.append(key, reqwest::header::HeaderValue::from_static(val));
+    }
+    reqwest::Client::builder().default_headers(headers).build()
+}
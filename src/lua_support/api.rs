@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use fend_core::Context;
 use mlua::prelude::*;
@@ -7,51 +10,137 @@ use scraper::{Html, Selector, node::Element};
 use tokio::sync::Mutex;
 use url::Url;
 
-use crate::Query;
+use super::transport::{FixtureRequest, Transport};
+use crate::{Error, Kind, Query};
 
 impl LuaUserData for Query {
     fn add_fields<F: LuaUserDataFields<Self>>(fields: &mut F) {
         fields.add_field_method_get("query", |_, this| Ok(this.query.clone()));
+        fields.add_field_method_get("kind", |_, this| Ok(this.kind));
         fields.add_field_method_get("page", |_, this| Ok(this.page));
         fields.add_field_method_get("safe", |_, this| Ok(this.safe.to_string().to_lowercase()));
+        fields.add_field_method_get("language", |_, this| Ok(this.language.clone()));
+        fields.add_field_method_get("region", |_, this| Ok(this.region.clone()));
+        fields.add_field_method_get("time_range", |_, this| {
+            Ok(this.time_range.map(|range| range.to_string()))
+        });
+        // Engines can't always tell the time, the sandbox has no `os`
+        fields.add_field_method_get("since", |_, this| {
+            Ok(this
+                .time_range
+                .map(|range| (chrono::Utc::now() - range.duration()).timestamp()))
+        });
+        // Parsed on every access so they follow rewrites of the query
+        fields.add_field_method_get("terms", |lua, this| {
+            terms_to_lua(lua, &searched_parser::Query::parse(&this.query).terms)
+        });
+        fields.add_field_method_get("plain", |_, this| {
+            Ok(searched_parser::Query::parse(&this.query).plain())
+        });
+
+        // Preprocessors rewrite queries in place
+        fields.add_field_method_set("query", |_, this, query: String| {
+            this.query = query;
+            Ok(())
+        });
+        fields.add_field_method_set("kind", |_, this, kind: Kind| {
+            this.kind = kind;
+            Ok(())
+        });
+        fields.add_field_method_set("page", |_, this, page: usize| {
+            this.page = page;
+            Ok(())
+        });
+        fields.add_field_method_set("safe", |_, this, safe: String| {
+            this.safe = safe
+                .parse()
+                .map_err(|_| LuaError::runtime(format!("invalid safe search level {safe}")))?;
+            Ok(())
+        });
+        fields.add_field_method_set("language", |_, this, language: Option<String>| {
+            this.language = language.map(|l| l.to_lowercase());
+            Ok(())
+        });
+        fields.add_field_method_set("region", |_, this, region: Option<String>| {
+            this.region = region.map(|r| r.to_uppercase());
+            Ok(())
+        });
+        fields.add_field_method_set("time_range", |_, this, range: Option<String>| {
+            this.time_range = range
+                .map(|range| {
+                    range
+                        .parse()
+                        .map_err(|_| LuaError::runtime(format!("invalid time range {range}")))
+                })
+                .transpose()?;
+            Ok(())
+        });
+    }
+}
+
+/// Turn parsed query terms into tables like `{ type = 'site', value = 'example.com', negated = false }`
+///
+/// `OR` groups have their members in `terms` instead of a `value`.
+fn terms_to_lua(lua: &Lua, terms: &[searched_parser::Term]) -> LuaResult<LuaTable> {
+    use searched_parser::TermKind;
+
+    let list = lua.create_table()?;
+    for term in terms {
+        let table = lua.create_table()?;
+        table.set("negated", term.negated)?;
+        let (kind, value) = match &term.kind {
+            TermKind::Word(value) => ("word", value),
+            TermKind::Phrase(value) => ("phrase", value),
+            TermKind::Site(value) => ("site", value),
+            TermKind::FileType(value) => ("filetype", value),
+            TermKind::InTitle(value) => ("intitle", value),
+            TermKind::Or(group) => {
+                table.set("type", "or")?;
+                table.set("terms", terms_to_lua(lua, group)?)?;
+                list.push(table)?;
+                continue;
+            }
+        };
+        table.set("type", kind)?;
+        table.set("value", value.as_str())?;
+        list.push(table)?;
     }
+    Ok(list)
 }
 
 /// Lua wrapper for [url::Url]
 pub struct UrlWrapper(Url);
 impl UrlWrapper {
     fn parse(_: &Lua, url: String) -> LuaResult<Self> {
-        Url::parse(&url).map(|x| UrlWrapper(x)).into_lua_err()
+        Url::parse(&url).map(UrlWrapper).into_lua_err()
     }
     fn parse_with_params(_: &Lua, (url, params): (String, LuaTable)) -> LuaResult<Self> {
         Url::parse_with_params(
             &url,
             params
                 .pairs::<String, String>()
-                .map(|x| x.unwrap())
-                .collect::<Vec<(String, String)>>(),
+                .collect::<LuaResult<Vec<(String, String)>>>()?,
         )
-        .map(|x| UrlWrapper(x))
+        .map(UrlWrapper)
         .into_lua_err()
     }
     fn from_template(_: &Lua, (template, values): (String, LuaTable)) -> LuaResult<Self> {
-        let values = HashMap::from_iter(
-            values
-                .pairs::<String, String>()
-                .map(|x| x.unwrap())
-                .map(|(k, v)| (k, v)),
-        );
-        Url::parse(&searched_parser::Url::parse(template.as_bytes()).build(values))
-            .map(|x| UrlWrapper(x))
-            .into_lua_err()
+        let values = values
+            .pairs::<String, String>()
+            .collect::<LuaResult<HashMap<String, String>>>()?;
+        let url = searched_parser::Url::try_parse(template.as_bytes())
+            .map_err(|err| LuaError::runtime(format!("bad URL template {template:?}: {err}")))?
+            .build(values)
+            .map_err(|err| LuaError::runtime(format!("can't fill URL template {template:?}: {err}")))?;
+        Url::parse(&url).map(UrlWrapper).into_lua_err()
     }
     fn params(lua: &Lua, this: &Self, _: ()) -> LuaResult<LuaValue> {
-        Ok(this
+        this
             .0
             .query_pairs()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect::<HashMap<String, String>>()
-            .into_lua(lua)?)
+            .into_lua(lua)
     }
     fn domain(lua: &Lua, this: &Self, _: ()) -> LuaResult<LuaValue> {
         Ok(if let Some(domain) = this.0.domain() {
@@ -91,29 +180,90 @@ impl LuaUserData for UrlWrapper {
 }
 
 /// Lua wrapper for [reqwest::Client]
-pub struct ClientWrapper(pub Client);
+pub struct ClientWrapper {
+    pub transport: Transport,
+    /// Sent with every request unless the engine sets its own
+    pub accept_language: Option<String>,
+}
 
 #[derive(Clone)]
 pub struct RequestBuilder {
-    client: Client,
+    transport: Transport,
     method: String,
     url: String,
     headers: HashMap<String, String>,
     form: Option<HashMap<String, String>>,
     json: Option<serde_json::Value>,
+    accept_language: Option<String>,
 }
 
 impl RequestBuilder {
-    pub fn new(client: Client, method: String, url: String) -> Self {
+    pub fn new(transport: Transport, method: String, url: String) -> Self {
         Self {
-            client,
+            transport,
             method,
             url,
+            accept_language: None,
             headers: HashMap::new(),
             form: None,
             json: None,
         }
     }
+
+    /// Send the request and read the response body
+    async fn fetch(&self) -> Result<String, Error> {
+        let request = FixtureRequest {
+            method: self.method.clone(),
+            url: self.url.clone(),
+            form: self.form.clone().map(BTreeMap::from_iter),
+            json: self.json.clone(),
+        };
+
+        match &self.transport {
+            Transport::Live(client) => self.send(client).await,
+            Transport::Record(client, store) => {
+                let body = self.send(client).await?;
+                store.save(&request, &body)?;
+                Ok(body)
+            }
+            Transport::Replay(store) => store.load(&request),
+        }
+    }
+
+    /// Send the request over the network
+    async fn send(&self, client: &Client) -> Result<String, Error> {
+        let method = reqwest::Method::from_bytes(self.method.as_bytes())
+            .map_err(|_| Error::Lua(format!("invalid HTTP method {}", self.method)))?;
+        let mut req = client.request(method, &self.url);
+
+        // Headers the plugin set itself win
+        let accept_language = self
+            .accept_language
+            .as_ref()
+            .filter(|_| !self.headers.keys().any(|k| k.eq_ignore_ascii_case("Accept-Language")));
+        if let Some(accept_language) = accept_language {
+            req = req.header("Accept-Language", accept_language);
+        }
+        for (k, v) in &self.headers {
+            req = req.header(k, v);
+        }
+
+        if let Some(form) = &self.form {
+            req = req.form(form);
+        }
+
+        if let Some(json) = &self.json {
+            req = req.json(json);
+        }
+
+        req.send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| Error::Http(e.without_url().to_string()))?
+            .text()
+            .await
+            .map_err(|e| Error::Http(format!("failed to read response: {}", e.without_url())))
+    }
 }
 
 impl LuaUserData for RequestBuilder {
@@ -144,26 +294,24 @@ impl LuaUserData for RequestBuilder {
                 LuaValue::Table(t) => {
                     // Convert table to HashMap
                     let mut map = HashMap::new();
-                    for pair in t.pairs::<String, LuaValue>() {
-                        if let Ok((k, v)) = pair {
-                            // Convert LuaValue to String or keep as JSON value
-                            let value = match v {
-                                LuaValue::String(s) => {
-                                    serde_json::Value::String(s.to_str()?.to_string())
-                                }
-                                LuaValue::Number(n) => serde_json::Value::Number(
-                                    serde_json::Number::from_f64(n).unwrap(),
-                                ),
-                                LuaValue::Boolean(b) => serde_json::Value::Bool(b),
-                                LuaValue::Nil => serde_json::Value::Null,
-                                _ => continue, // Skip other types
-                            };
-                            map.insert(k, value);
-                        }
+                    for (k, v) in t.pairs::<String, LuaValue>().flatten() {
+                        // Convert LuaValue to String or keep as JSON value
+                        let value = match v {
+                            LuaValue::String(s) => {
+                                serde_json::Value::String(s.to_str()?.to_string())
+                            }
+                            LuaValue::Number(n) => serde_json::Value::Number(
+                                serde_json::Number::from_f64(n).unwrap(),
+                            ),
+                            LuaValue::Boolean(b) => serde_json::Value::Bool(b),
+                            LuaValue::Nil => serde_json::Value::Null,
+                            _ => continue, // Skip other types
+                        };
+                        map.insert(k, value);
                     }
                     serde_json::Value::Object(serde_json::Map::from_iter(map))
                 }
-                LuaValue::String(s) => serde_json::from_str(&s.to_str()?.to_string())
+                LuaValue::String(s) => serde_json::from_str(s.to_str()?.as_ref())
                     .unwrap_or(serde_json::Value::Null),
                 _ => serde_json::Value::Null,
             };
@@ -173,68 +321,13 @@ impl LuaUserData for RequestBuilder {
         });
 
         methods.add_async_method("send", |_, this, _: ()| async move {
-            let mut req = this.client.request(
-                reqwest::Method::from_bytes(this.method.as_bytes()).unwrap(),
-                &this.url,
-            );
-
-            // Use references to avoid moves
-            for (k, v) in &this.headers {
-                req = req.header(k, v);
-            }
-
-            if let Some(form) = &this.form {
-                req = req.form(form);
-            }
-
-            if let Some(json) = &this.json {
-                req = req.json(json);
-            }
-
-            match req.send().await {
-                Ok(res) => match res.text().await {
-                    Ok(text) => Ok(text),
-                    Err(e) => Err(LuaError::RuntimeError(format!(
-                        "Failed to read response: {}",
-                        e
-                    ))),
-                },
-                Err(e) => Err(LuaError::RuntimeError(format!("Request failed: {}", e))),
-            }
+            this.fetch().await.map_err(LuaError::external)
         });
 
         methods.add_async_method("html", |_, this, _: ()| async move {
-            let mut req = this.client.request(
-                reqwest::Method::from_bytes(this.method.as_bytes()).unwrap(),
-                &this.url,
-            );
-
-            // Add headers
-            for (k, v) in &this.headers {
-                req = req.header(k, v);
-            }
-
-            if let Some(form) = &this.form {
-                req = req.form(form);
-            }
-
-            if let Some(json) = &this.json {
-                req = req.json(json);
-            }
-
-            match req.send().await {
-                Ok(res) => match res.text().await {
-                    Ok(text) => {
-                        let html = Html::parse_document(&text);
-                        Ok(Scraper(Arc::new(Mutex::new(html))))
-                    }
-                    Err(e) => Err(LuaError::RuntimeError(format!(
-                        "Failed to read response: {}",
-                        e
-                    ))),
-                },
-                Err(e) => Err(LuaError::RuntimeError(format!("Request failed: {}", e))),
-            }
+            let text = this.fetch().await.map_err(LuaError::external)?;
+            let html = Html::parse_document(&text);
+            Ok(Scraper(Arc::new(Mutex::new(html))))
         });
     }
 }
@@ -243,7 +336,10 @@ impl LuaUserData for ClientWrapper {
     fn add_methods<M: LuaUserDataMethods<Self>>(methods: &mut M) {
         // Only keep the builder method
         methods.add_method("req", |_, this, (method, url): (String, String)| {
-            Ok(RequestBuilder::new(this.0.clone(), method, url))
+            Ok(RequestBuilder {
+                accept_language: this.accept_language.clone(),
+                ..RequestBuilder::new(this.transport.clone(), method, url)
+            })
         });
     }
 }
@@ -262,7 +358,8 @@ impl LuaUserData for Scraper {
             this: LuaUserDataRef<Scraper>,
             selector: String,
         ) -> LuaResult<LuaTable> {
-            let sel = Selector::parse(&selector).unwrap();
+            let sel = Selector::parse(&selector)
+                .map_err(|e| LuaError::runtime(format!("invalid selector {selector:?}: {e}")))?;
             lua.create_sequence_from(
                 this.0
                     .lock()
@@ -334,6 +431,27 @@ pub fn add_ranker(lua: &Lua, (name, callback): (String, LuaFunction)) -> LuaResu
 
     Ok(())
 }
+pub fn add_preprocessor(lua: &Lua, (name, callback): (String, LuaFunction)) -> LuaResult<()> {
+    lua.globals()
+        .get::<LuaTable>("__searched_preprocessors__")?
+        .set(name, callback.clone())?;
+
+    Ok(())
+}
+pub fn add_postprocessor(lua: &Lua, (name, callback): (String, LuaFunction)) -> LuaResult<()> {
+    lua.globals()
+        .get::<LuaTable>("__searched_postprocessors__")?
+        .set(name, callback.clone())?;
+
+    Ok(())
+}
+pub fn add_suggester(lua: &Lua, (name, callback): (String, LuaFunction)) -> LuaResult<()> {
+    lua.globals()
+        .get::<LuaTable>("__searched_suggesters__")?
+        .set(name, callback.clone())?;
+
+    Ok(())
+}
 pub fn stringify_params(_: &Lua, params: LuaTable) -> LuaResult<String> {
     Ok(params
         .pairs::<String, String>()
@@ -342,12 +460,13 @@ pub fn stringify_params(_: &Lua, params: LuaTable) -> LuaResult<String> {
         .join("&"))
 }
 pub fn parse_json(lua: &Lua, raw: String) -> LuaResult<LuaValue> {
-    let json: serde_json::Value = serde_json::from_str(&raw).into_lua_err()?;
+    let json: serde_json::Value = serde_json::from_str(&raw)
+        .map_err(|e| LuaError::external(Error::Parse(e.to_string())))?;
     lua.to_value(&json)
 }
 pub fn fend_eval(_: &Lua, input: String) -> LuaResult<String> {
     Ok(fend_core::evaluate(&input, &mut Context::new())
-        .unwrap()
+        .map_err(LuaError::runtime)?
         .get_main_result()
         .to_string())
 }
@@ -0,0 +1,227 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{ProviderResponse, Query};
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    response: ProviderResponse,
+    timestamp: u64,
+}
+
+/// Provider response cache stored in sled
+#[derive(Clone)]
+pub(super) struct ResponseCache {
+    tree: sled::Tree,
+}
+impl ResponseCache {
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            tree: db.open_tree("provider_responses")?,
+        })
+    }
+
+    fn key(provider: &str, query: &Query, extra: &BTreeMap<String, String>) -> Option<Vec<u8>> {
+        bincode::serialize(&(
+            provider,
+            &query.query,
+            query.kind,
+            query.page,
+            query.safe,
+            &query.language,
+            &query.region,
+            query.time_range,
+            extra,
+        ))
+        .ok()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+    }
+
+    /// Get the cached response of a provider if it is younger than `ttl` seconds
+    ///
+    /// Responses are kept apart by the engine options users changed.
+    pub fn get(
+        &self,
+        provider: &str,
+        query: &Query,
+        extra: &BTreeMap<String, String>,
+        ttl: u64,
+    ) -> Option<ProviderResponse> {
+        let key = Self::key(provider, query, extra)?;
+        let cached = bincode::deserialize::<CachedResponse>(&self.tree.get(&key).ok()??).ok()?;
+
+        if Self::now().saturating_sub(cached.timestamp) < ttl {
+            Some(cached.response)
+        } else {
+            let _ = self.tree.remove(&key);
+            None
+        }
+    }
+
+    pub fn insert(
+        &self,
+        provider: &str,
+        query: &Query,
+        extra: &BTreeMap<String, String>,
+        response: &ProviderResponse,
+    ) {
+        let cached = CachedResponse {
+            response: response.clone(),
+            timestamp: Self::now(),
+        };
+
+        if let (Some(key), Ok(encoded)) = (Self::key(provider, query, extra), bincode::serialize(&cached)) {
+            let _ = self.tree.insert(key, encoded);
+        }
+    }
+}
+
+/// Prefix, language and region of cached completions
+type SuggestionKey = (String, Option<String>, Option<String>);
+
+/// Merged completions by prefix, kept in memory
+///
+/// People type the same starts of queries over and over, so these are
+/// asked for far more often than whole searches. Only the `capacity` most
+/// recently used prefixes are kept, completions are quick to get again.
+#[derive(Clone)]
+pub(super) struct SuggestionCache {
+    inner: Arc<Mutex<SuggestionLru>>,
+}
+
+struct SuggestionLru {
+    capacity: usize,
+    /// Bumped on every use, orders entries by when they were last used
+    clock: u64,
+    entries: HashMap<SuggestionKey, CachedSuggestions>,
+    by_use: BTreeMap<u64, SuggestionKey>,
+}
+
+struct CachedSuggestions {
+    suggestions: Vec<String>,
+    cached_at: Instant,
+    last_used: u64,
+}
+
+impl SuggestionCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(SuggestionLru {
+                capacity,
+                clock: 0,
+                entries: HashMap::new(),
+                by_use: BTreeMap::new(),
+            })),
+        }
+    }
+
+    fn key(prefix: &str, language: Option<&str>, region: Option<&str>) -> SuggestionKey {
+        (prefix.to_owned(), language.map(str::to_owned), region.map(str::to_owned))
+    }
+
+    /// Get the completions for a prefix if they are younger than `ttl` seconds
+    pub fn get(&self, prefix: &str, language: Option<&str>, region: Option<&str>, ttl: u64) -> Option<Vec<String>> {
+        let key = Self::key(prefix, language, region);
+        let mut lru = self.inner.lock().unwrap();
+        let lru = &mut *lru;
+
+        let cached = lru.entries.get_mut(&key)?;
+        if cached.cached_at.elapsed() >= Duration::from_secs(ttl) {
+            lru.by_use.remove(&cached.last_used);
+            lru.entries.remove(&key);
+            return None;
+        }
+
+        lru.clock += 1;
+        lru.by_use.remove(&cached.last_used);
+        cached.last_used = lru.clock;
+        lru.by_use.insert(lru.clock, key);
+        Some(cached.suggestions.clone())
+    }
+
+    pub fn insert(&self, prefix: &str, language: Option<&str>, region: Option<&str>, suggestions: &[String]) {
+        let key = Self::key(prefix, language, region);
+        let mut lru = self.inner.lock().unwrap();
+        if lru.capacity == 0 {
+            return;
+        }
+
+        lru.clock += 1;
+        let cached = CachedSuggestions {
+            suggestions: suggestions.to_vec(),
+            cached_at: Instant::now(),
+            last_used: lru.clock,
+        };
+        if let Some(old) = lru.entries.insert(key.clone(), cached) {
+            lru.by_use.remove(&old.last_used);
+        }
+        let clock = lru.clock;
+        lru.by_use.insert(clock, key);
+
+        while lru.entries.len() > lru.capacity {
+            let Some((_, oldest)) = lru.by_use.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_the_most_recently_used() {
+        let cache = SuggestionCache::new(2);
+        cache.insert("a", None, None, &words(&["apple"]));
+        cache.insert("b", None, None, &words(&["banana"]));
+        assert!(cache.get("a", None, None, 60).is_some());
+
+        cache.insert("c", None, None, &words(&["cherry"]));
+        assert_eq!(cache.get("a", None, None, 60), Some(words(&["apple"])));
+        assert_eq!(cache.get("b", None, None, 60), None);
+        assert_eq!(cache.get("c", None, None, 60), Some(words(&["cherry"])));
+    }
+
+    #[test]
+    fn keys_include_language_and_region() {
+        let cache = SuggestionCache::new(10);
+        cache.insert("a", Some("en"), Some("US"), &words(&["apple"]));
+        assert_eq!(cache.get("a", Some("de"), Some("US"), 60), None);
+        assert_eq!(cache.get("a", Some("en"), None, 60), None);
+        assert!(cache.get("a", Some("en"), Some("US"), 60).is_some());
+    }
+
+    #[test]
+    fn forgets_expired_entries() {
+        let cache = SuggestionCache::new(10);
+        cache.insert("a", None, None, &words(&["apple"]));
+        assert_eq!(cache.get("a", None, None, 0), None);
+        assert_eq!(cache.inner.lock().unwrap().entries.len(), 0);
+        assert!(cache.inner.lock().unwrap().by_use.is_empty());
+    }
+
+    #[test]
+    fn replacing_keeps_one_entry() {
+        let cache = SuggestionCache::new(10);
+        cache.insert("a", None, None, &words(&["apple"]));
+        cache.insert("a", None, None, &words(&["avocado"]));
+        assert_eq!(cache.get("a", None, None, 60), Some(words(&["avocado"])));
+        let lru = cache.inner.lock().unwrap();
+        assert_eq!((lru.entries.len(), lru.by_use.len()), (1, 1));
+    }
+}
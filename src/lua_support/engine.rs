@@ -1,43 +1,202 @@
 use std::{
-    collections::HashMap,
-    fs::{File, read_dir},
-    io::Read,
-    time::Instant,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{read_dir, read_to_string},
+    path::Path,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use mlua::prelude::*;
 use reqwest::Client;
-use tokio::task::JoinSet;
-use url::Url;
+use tokio::{sync::mpsc, task::JoinSet, time::timeout_at};
 
-use super::api::*;
-use crate::{Error, Query, SearchResult, config::ProvidersConfig, settings::Settings};
+use super::{
+    api::*,
+    cache::{ResponseCache, SuggestionCache},
+    health::{HealthTracker, ProviderHealth},
+    limits::call_limited,
+    merger,
+    meta,
+    operators,
+    pool::{LuaPool, PooledLua},
+    ranker,
+    recency,
+    transport::Transport,
+};
+use crate::{
+    Error, Kind, ProviderError, ProviderOptions, ProviderResponse, Query, SafeSearch, SearchOptions,
+    SearchResponse, SearchResult, SearchStream,
+    bangs::{self, BangTarget},
+    config::{
+        BangsConfig, CfgPaginationSupport, CfgPlugins, CfgProvider, CfgProviderFeatures,
+        CfgRanking, CfgSafeSearchSupport, CfgSearch, CfgStage, CfgSuggest, Config, ProvidersConfig,
+    },
+};
+
+/// Directories under `plugins/` that hold Lua plugins, in the order they load
+///
+/// `lib` goes first, the helpers it defines are shared by the other plugins.
+pub(super) const PLUGIN_DIRS: &[&str] = &["lib", "engines", "preprocessors", "ranking", "postprocessors", "suggesters"];
 
-/// A single-threaded plugin engine
+/// Globals plugins register their callbacks in
+pub(super) const REGISTRIES: &[&str] = &[
+    "__searched_engines__",
+    "__searched_preprocessors__",
+    "__searched_mergers__",
+    "__searched_rankers__",
+    "__searched_postprocessors__",
+    "__searched_suggesters__",
+];
+
+/// How a search goes on after its bang and preprocessors
+struct Prepared {
+    /// Send the user here instead of searching
+    redirect: Option<String>,
+    /// The bang that limited the search to one provider
+    bang: Option<String>,
+}
+
+/// What a preprocessor wants done with the search
+#[derive(Deserialize, Default)]
+struct Preprocessed {
+    /// Ask these providers instead
+    providers: Option<Vec<String>>,
+    /// Send the user here instead of searching
+    redirect: Option<String>,
+}
+
+/// A plugin engine backed by a pool of Lua states
 #[derive(Clone)]
 pub struct PluginEngine {
-    lua: Lua,
-    client: Client,
-    #[cfg(not(feature = "hot_reload"))]
-    providers: ProvidersConfig,
+    /// Swapped out as a whole when plugins are reloaded
+    pool: Arc<RwLock<Arc<LuaPool>>>,
+    transport: Transport,
+    cache: ResponseCache,
+    suggestion_cache: SuggestionCache,
+    health: HealthTracker,
+    search_cfg: CfgSearch,
+    ranking_cfg: CfgRanking,
+    suggest_cfg: CfgSuggest,
+    /// Running time each plugin call gets
+    call_limit: Duration,
+    providers: Arc<RwLock<Arc<ProvidersConfig>>>,
+    bangs: Arc<BangsConfig>,
 }
 impl PluginEngine {
     /// Initialize a new engine for running plugins
-    pub async fn new(client: Client) -> Result<Self, Box<dyn core::error::Error>> {
-        #[cfg(not(feature = "hot_reload"))]
-        let providers = ProvidersConfig::load("plugins/providers.toml");
+    pub async fn new(
+        client: Client,
+        config: &Config,
+        db: &sled::Db,
+    ) -> Result<Self, Box<dyn core::error::Error>> {
+        let providers = Arc::new(RwLock::new(Arc::new(ProvidersConfig::load(
+            "plugins/providers.toml",
+        ))));
+
+        let pool_size = config.plugins.pool_size();
+        debug!("initializing plugin engine with {pool_size} Lua states...");
+
+        let mut states = Vec::with_capacity(pool_size);
+        for _ in 0..pool_size {
+            states.push(Self::create_lua(&config.plugins).await?);
+        }
+
+        debug!("Initialized plugin engine!");
+
+        let pool = Arc::new(RwLock::new(Arc::new(LuaPool::new(states))));
+        let call_limit = Duration::from_millis(config.plugins.call_time_limit);
+
+        #[cfg(feature = "hot_reload")]
+        if let Err(err) = super::reload::watch(pool.clone(), providers.clone(), config.plugins.clone()) {
+            warn!("couldn't watch plugins for changes, hot reloading is off: {err}");
+        }
+
+        let search_cfg = config.search.clone();
+        Ok(Self {
+            pool,
+            transport: Transport::Live(client),
+            cache: ResponseCache::open(db)?,
+            suggestion_cache: SuggestionCache::new(config.suggest.cache_size),
+            health: HealthTracker::new(&search_cfg),
+            search_cfg,
+            ranking_cfg: config.ranking.clone(),
+            suggest_cfg: config.suggest.clone(),
+            call_limit,
+            providers,
+            bangs: Arc::new(Self::load_bangs("plugins/bangs.toml")),
+        })
+    }
+
+    /// Send engine requests somewhere else, like a fixture store
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Load bangs, leaving out any whose URL template can't be used
+    fn load_bangs(path: &str) -> BangsConfig {
+        let mut bangs = BangsConfig::try_load(path).unwrap_or_else(|err| {
+            warn!("no bangs loaded from {path}: {err}");
+            BangsConfig::default()
+        });
+
+        bangs.0.retain(|name, bang| {
+            let usable = bang.url.as_deref().is_none_or(bangs::is_valid_template);
+            if !usable {
+                warn!("leaving out bang !{name}, its URL template can't be used");
+            }
+            usable
+        });
+        bangs.0 = bangs
+            .0
+            .into_iter()
+            .map(|(name, bang)| (name.to_lowercase(), bang))
+            .collect();
+
+        bangs
+    }
+
+    /// Create a Lua state with our API and every plugin loaded
+    async fn create_lua(cfg: &CfgPlugins) -> Result<Lua, Error> {
+        let lua = Self::new_lua(cfg)?;
+
+        debug!("loading engines...");
+
+        // Load engines
+        Self::load_plugins(&lua, Duration::from_millis(cfg.call_time_limit)).await?;
+
+        debug!("loaded engines!");
+
+        Ok(lua)
+    }
+
+    /// Create a Lua state with our API, but no plugins
+    pub(super) fn new_lua(cfg: &CfgPlugins) -> LuaResult<Lua> {
+        let lua = if cfg.sandbox {
+            #[allow(unused_mut)]
+            let mut blocked = LuaStdLib::OS | LuaStdLib::PACKAGE;
+            #[cfg(not(feature = "__luau-jit"))]
+            {
+                blocked |= LuaStdLib::IO;
+            }
+            // StdLib has no `!`, clear the bits instead of flipping them
+            let libs = LuaStdLib::ALL_SAFE ^ (LuaStdLib::ALL_SAFE & blocked);
+            let lua = Lua::new_with(libs, LuaOptions::default())?;
 
-        debug!("initializing plugin engine...");
+            // These read files straight from disk
+            lua.globals().raw_remove("dofile")?;
+            lua.globals().raw_remove("loadfile")?;
 
-        let lua = Lua::new();
+            lua
+        } else {
+            Lua::new()
+        };
+        lua.set_memory_limit(cfg.memory_limit * 1024 * 1024)?;
 
         // Add Lua global variables we need
-        lua.globals()
-            .set("__searched_engines__", lua.create_table()?)?;
-        lua.globals()
-            .set("__searched_mergers__", lua.create_table()?)?;
-        lua.globals()
-            .set("__searched_rankers__", lua.create_table()?)?;
+        for registry in REGISTRIES {
+            lua.globals().set(*registry, lua.create_table()?)?;
+        }
 
         // Add Lua interfaces
         lua.globals()
@@ -53,10 +212,16 @@ impl PluginEngine {
         // Add standalone Lua functions
         lua.globals()
             .set("add_engine", lua.create_function(add_engine)?)?;
+        lua.globals()
+            .set("add_preprocessor", lua.create_function(add_preprocessor)?)?;
         lua.globals()
             .set("add_merger", lua.create_function(add_merger)?)?;
         lua.globals()
             .set("add_ranker", lua.create_function(add_ranker)?)?;
+        lua.globals()
+            .set("add_postprocessor", lua.create_function(add_postprocessor)?)?;
+        lua.globals()
+            .set("add_suggester", lua.create_function(add_suggester)?)?;
         lua.globals()
             .set("stringify_params", lua.create_function(stringify_params)?)?;
         lua.globals()
@@ -64,220 +229,1053 @@ impl PluginEngine {
         lua.globals()
             .set("fend_eval", lua.create_function(fend_eval)?)?;
 
-        debug!("Initialized plugin engine! loading engines...");
+        #[cfg(not(feature = "__luau-jit"))]
+        super::limits::limit_coroutines(&lua)?;
 
-        // Load engines
-        Self::load_plugins(&lua).await;
+        Ok(lua)
+    }
 
-        debug!("loaded engines!");
+    /// Find problems with providers.toml, bangs.toml and the plugins
+    ///
+    /// Everything is loaded from scratch, nothing has to be running. Returns
+    /// a description of every problem found.
+    pub async fn check(config: &Config) -> Vec<String> {
+        let mut problems = Vec::new();
 
-        Ok(Self {
-            lua,
-            client,
-            #[cfg(not(feature = "hot_reload"))]
-            providers,
+        let providers = match ProvidersConfig::try_load("plugins/providers.toml") {
+            Ok(providers) => providers,
+            Err(err) => return vec![format!("plugins/providers.toml can't be loaded: {err}")],
+        };
+
+        let lua = match Self::new_lua(&config.plugins) {
+            Ok(lua) => lua,
+            Err(err) => return vec![format!("couldn't create a Lua state: {err}")],
+        };
+        let call_limit = Duration::from_millis(config.plugins.call_time_limit);
+        for plugin_kind in PLUGIN_DIRS {
+            let Ok(dir) = read_dir(format!("plugins/{plugin_kind}")) else {
+                continue;
+            };
+            let mut paths = dir.flatten().map(|entry| entry.path()).collect::<Vec<_>>();
+            paths.sort_unstable();
+            for path in paths {
+                if let Err(err) = Self::load_plugin(&lua, &path, call_limit).await {
+                    problems.push(format!("{} doesn't load: {err}", path.display()));
+                }
+            }
+        }
+
+        let names = |registry| {
+            Self::registered(&lua, registry)
+                .map(|registered| registered.into_iter().map(|(name, _)| name).collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        let engines = names("__searched_engines__");
+        let mergers = names("__searched_mergers__");
+        let rankers = names("__searched_rankers__");
+        let suggesters = names("__searched_suggesters__");
+
+        let mut provider_names = providers.0.keys().collect::<Vec<_>>();
+        provider_names.sort_unstable();
+        for name in provider_names {
+            let provider = &providers.0[name];
+            let engine = provider.engine.as_ref().unwrap_or(name);
+            if !engines.contains(engine) {
+                problems.push(format!("provider {name} uses engine {engine}, which no plugin registers"));
+            }
+            if provider.kinds.is_empty() {
+                problems.push(format!("provider {name} has no kinds, nothing will ask it"));
+            }
+
+            let mut settings = provider.settings.iter().collect::<Vec<_>>();
+            settings.sort_unstable_by_key(|(key, _)| *key);
+            for (key, setting) in settings {
+                let default = provider
+                    .extra
+                    .as_ref()
+                    .and_then(|extra| extra.get(key))
+                    .and_then(|value| value.as_str());
+                match default {
+                    Some(default) if !setting.allows(default) => problems.push(format!(
+                        "provider {name} defaults {key} to {default:?}, which isn't one of its choices"
+                    )),
+                    None => problems.push(format!("provider {name} lets users change {key}, but has no default for it")),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut kinds = config.search.providers.iter().collect::<Vec<_>>();
+        kinds.sort_unstable_by_key(|(kind, _)| format!("{kind:?}"));
+        for (kind, names) in kinds {
+            for name in names.iter().filter(|name| !providers.0.contains_key(*name)) {
+                problems.push(format!("search.providers for {kind:?} lists {name}, which isn't a provider"));
+            }
+        }
+
+        let ranking = &config.ranking;
+        let stages = std::iter::once((&ranking.merger, &ranking.ranker))
+            .map(|(merger, ranker)| (Some(merger), Some(ranker)))
+            .chain(ranking.kinds.values().map(|k| (k.merger.as_ref(), k.ranker.as_ref())));
+        for (merger, ranker) in stages {
+            if let Some(merger) = merger.filter(|m| *m != merger::NAME && !mergers.contains(m)) {
+                problems.push(format!("no merger named {merger}"));
+            }
+            if let Some(ranker) = ranker.filter(|r| *r != ranker::NAME && !rankers.contains(r)) {
+                problems.push(format!("no ranker named {ranker}"));
+            }
+        }
+
+        for suggester in config.suggest.suggesters.iter().flatten() {
+            if !suggesters.contains(suggester) {
+                problems.push(format!("suggest.suggesters lists {suggester}, which no plugin registers"));
+            }
+        }
+
+        match BangsConfig::try_load("plugins/bangs.toml") {
+            Ok(bangs) => {
+                let mut bangs = bangs.0.into_iter().collect::<Vec<_>>();
+                bangs.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+                for (name, bang) in bangs {
+                    match (&bang.url, &bang.provider) {
+                        (Some(url), _) if !bangs::is_valid_template(url) => {
+                            problems.push(format!("bang !{name} has an unusable URL template {url:?}"))
+                        }
+                        (None, Some(provider)) if !providers.0.contains_key(provider) => {
+                            problems.push(format!("bang !{name} searches {provider}, which isn't a provider"))
+                        }
+                        (None, None) => problems.push(format!("bang !{name} has neither a url nor a provider")),
+                        _ => {}
+                    }
+                }
+            }
+            Err(err) => problems.push(format!("plugins/bangs.toml can't be loaded: {err}")),
+        }
+
+        problems
+    }
+
+    /// Run every plugin, stopping at the first one that fails
+    pub async fn load_plugins(lua: &Lua, call_limit: Duration) -> Result<(), Error> {
+        for plugin_kind in PLUGIN_DIRS {
+            // Not every kind of plugin has to be present
+            let Ok(dir) = read_dir(format!("plugins/{plugin_kind}")) else {
+                continue;
+            };
+
+            // Sorted, so every state and every reload runs them in the same order
+            let mut paths = dir.flatten().map(|entry| entry.path()).collect::<Vec<_>>();
+            paths.sort_unstable();
+            for path in paths {
+                Self::load_plugin(lua, &path, call_limit)
+                    .await
+                    .map_err(|err| Error::PluginLoad(path.display().to_string(), Box::new(err)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run a single plugin file
+    pub(super) async fn load_plugin(
+        lua: &Lua,
+        path: &Path,
+        call_limit: Duration,
+    ) -> Result<(), Error> {
+        let name = path
+            .file_stem()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Lua(format!("bad plugin file name {}", path.display())))?;
+
+        debug!("loading {name}...");
+        let load_st = Instant::now();
+
+        let src = read_to_string(path)
+            .map_err(|err| Error::Lua(format!("couldn't read {}: {err}", path.display())))?;
+        let rel = path.strip_prefix("plugins").unwrap_or(path);
+        Self::run_plugin(lua, rel, &src, call_limit).await?;
+
+        debug!("loaded {name} in {:?}!", load_st.elapsed());
+
+        Ok(())
+    }
+
+    /// Run the source of the plugin at `rel`, relative to `plugins/`
+    pub(super) async fn run_plugin(
+        lua: &Lua,
+        rel: &Path,
+        src: &str,
+        call_limit: Duration,
+    ) -> Result<(), Error> {
+        let name = rel.file_stem().and_then(|name| name.to_str()).unwrap_or_default();
+        let chunk = lua.load(src).set_name(name).into_function()?;
+
+        #[cfg(feature = "hot_reload")]
+        let before = super::reload::snapshot(lua)?;
+        let res = call_limited::<()>(lua, &chunk, (), call_limit).await;
+        // Even a plugin that failed halfway has to be taken out on reload
+        #[cfg(feature = "hot_reload")]
+        super::reload::claim(lua, rel, &before)?;
+
+        res
+    }
+
+    /// Take a Lua state from the current pool
+    async fn lua(&self) -> PooledLua {
+        loop {
+            let pool = self.pool.read().unwrap().clone();
+            let lua = pool.get().await;
+
+            // A reload may have swapped the pool out while we waited
+            if Arc::ptr_eq(&pool, &self.pool.read().unwrap()) {
+                return lua;
+            }
+        }
+    }
+
+    /// Get the provider config
+    pub fn providers(&self) -> Arc<ProvidersConfig> {
+        self.providers.read().unwrap().clone()
+    }
+
+    /// Get the providers asked for a kind unless the search picks others
+    pub fn default_providers(&self, kind: Kind) -> Vec<String> {
+        match self.search_cfg.providers.get(&kind) {
+            Some(providers) => providers.clone(),
+            None => self.providers().for_kind(kind),
+        }
+    }
+
+    /// Get the providers to ask for a kind, after the user's choices
+    ///
+    /// Users can skip default providers and add others that list the kind.
+    pub fn providers_for(&self, kind: Kind, options: &SearchOptions) -> Vec<String> {
+        let enabled = |name: &String| options.providers.get(name).and_then(|p| p.enabled);
+
+        let mut providers = self.default_providers(kind);
+        providers.retain(|name| enabled(name) != Some(false));
+        for name in self.providers().for_kind(kind) {
+            if enabled(&name) == Some(true) && !providers.contains(&name) {
+                providers.push(name);
+            }
+        }
+
+        providers
+    }
+
+    /// Engine options a user changed that the provider lets them change
+    fn user_extra(cfg: &CfgProvider, options: Option<&ProviderOptions>) -> BTreeMap<String, String> {
+        options
+            .map(|options| {
+                options
+                    .extra
+                    .iter()
+                    .filter(|(key, value)| cfg.settings.get(*key).is_some_and(|s| s.allows(value)))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the health of every provider that has been queried so far
+    pub fn health(&self) -> HashMap<String, ProviderHealth> {
+        self.health.snapshot()
+    }
+
+    pub async fn search(
+        &self,
+        mut query: Query,
+        mut providers: Vec<String>,
+        options: &SearchOptions,
+    ) -> Result<SearchResponse, Error> {
+        let Prepared { redirect, bang } = self.prepare(&mut query, &mut providers, options).await?;
+        if redirect.is_some() {
+            return Ok(SearchResponse {
+                query,
+                redirect,
+                bang,
+                ..Default::default()
+            });
+        }
+
+        self.check_stages(query.kind, options).await?;
+        let response = self.search_multi(query.clone(), providers, options, None).await?;
+        self.finish(&query, &bang, response, options).await
+    }
+
+    /// Search like [`Self::search`], sending the results again every time
+    /// a provider answers
+    ///
+    /// Bangs and preprocessors run before this returns, so redirects are
+    /// known before anything is sent to the user.
+    pub async fn search_stream(
+        &self,
+        mut query: Query,
+        mut providers: Vec<String>,
+        options: SearchOptions,
+    ) -> Result<SearchStream, Error> {
+        let (tx, updates) = mpsc::channel(1);
+
+        let Prepared { redirect, bang } = self.prepare(&mut query, &mut providers, &options).await?;
+        if redirect.is_some() {
+            return Ok(SearchStream {
+                query,
+                redirect,
+                bang,
+                updates,
+            });
+        }
+
+        self.check_stages(query.kind, &options).await?;
+
+        let eng = self.clone();
+        let stream_query = query.clone();
+        let stream_bang = bang.clone();
+        tokio::spawn(async move {
+            let (query, bang) = (stream_query, stream_bang);
+            let (progress_tx, mut progress) = mpsc::unbounded_channel();
+            let search = eng.search_multi(query.clone(), providers, &options, Some(progress_tx));
+            tokio::pin!(search);
+
+            loop {
+                tokio::select! {
+                    response = &mut search => {
+                        let response = match response {
+                            Ok(response) => eng.finish(&query, &bang, response, &options).await,
+                            Err(err) => Err(err),
+                        };
+                        let _ = tx.send(response).await;
+                        break;
+                    }
+                    Some(mut response) = progress.recv() => {
+                        // Only the latest state is worth ranking
+                        while let Ok(newer) = progress.try_recv() {
+                            response = newer;
+                        }
+                        // The final state is sent once the search returns
+                        if response.pending.is_empty() {
+                            continue;
+                        }
+
+                        let response = eng.finish(&query, &bang, response, &options).await;
+                        let failed = response.is_err();
+                        // Stop searching once nobody is listening
+                        if tx.send(response).await.is_err() || failed {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(SearchStream {
+            query,
+            redirect: None,
+            bang,
+            updates,
         })
     }
 
-    pub async fn load_plugins(lua: &Lua) {
-        for plugin_kind in ["engines", "ranking"] {
-        for path in read_dir(&format!("plugins/{plugin_kind}")).unwrap() {
-            if let Ok(path) = path {
-                // Do war crime level code
-                let name = path
-                    .path()
-                    .file_stem()
-                    .expect("bad file path")
-                    .to_str()
-                    .expect("filename should be utf-8")
-                    .to_string();
+    /// Make sure the merger and ranker a search asks for exist
+    ///
+    /// A wrong name in the URL should fail the search before any provider
+    /// is asked, not fall back after the fact.
+    async fn check_stages(&self, kind: Kind, options: &SearchOptions) -> Result<(), Error> {
+        let merger = self.ranking_cfg.merger(kind).with_name(options.merger.as_ref()).name;
+        let ranker = self.ranking_cfg.ranker(kind).with_name(options.ranker.as_ref()).name;
 
-                debug!("loading {name}...");
-                let load_st = Instant::now();
+        let lua = self.lua().await;
+        let registered = |registry: &str, name: &str| -> Result<bool, Error> {
+            Ok(lua.globals().get::<LuaTable>(registry)?.contains_key(name)?)
+        };
 
-                // Read the source code into buf
-                let mut buf = String::new();
-                let mut f = File::open(path.path()).unwrap();
-                f.read_to_string(&mut buf).unwrap();
+        if merger != merger::NAME && !registered("__searched_mergers__", &merger)? {
+            return Err(Error::MergerNotFound(merger));
+        }
+        if ranker != ranker::NAME && !registered("__searched_rankers__", &ranker)? {
+            return Err(Error::RankerNotFound(ranker));
+        }
+        Ok(())
+    }
+
+    /// Merge, rank and postprocess what the providers returned
+    ///
+    /// A failing Lua merger or ranker is replaced by the native one, and the
+    /// failure is reported with the results.
+    async fn finish(
+        &self,
+        query: &Query,
+        bang: &Option<String>,
+        mut response: SearchResponse,
+        options: &SearchOptions,
+    ) -> Result<SearchResponse, Error> {
+        let merger = self.ranking_cfg.merger(query.kind).with_name(options.merger.as_ref());
+        let mut ranker = self.ranking_cfg.ranker(query.kind).with_name(options.ranker.as_ref());
+        ranker::apply_weights(
+            &mut ranker.options,
+            options
+                .providers
+                .iter()
+                .filter_map(|(name, p)| Some((name, p.weight?))),
+        );
 
-                lua.load(&buf).exec_async().await.unwrap();
+        let results = std::mem::take(&mut response.results);
+        let merger_name = merger.name.clone();
+        let merged = match self.merge(merger, results.clone()).await {
+            Ok(merged) => merged,
+            Err(err) => {
+                warn!("merger {merger_name} failed, using {} instead: {err}", merger::NAME);
+                response.warnings.push(format!("merger {merger_name} failed: {err}"));
+                merger::merge(results)
+            }
+        };
+        let merged = operators::enforce(&query.query, merged);
+        let merged = recency::enforce(query.time_range, merged);
 
-                debug!("loaded {name} in {:?}!", load_st.elapsed());
+        let ranker_name = ranker.name.clone();
+        let native_weights = ranker.options.clone();
+        let ranked = match self.rank(ranker, merged.clone()).await {
+            Ok(ranked) => ranked,
+            Err(err) => {
+                warn!("ranker {ranker_name} failed, using {} instead: {err}", ranker::NAME);
+                response.warnings.push(format!("ranker {ranker_name} failed: {err}"));
+                let weights = ranker::rank(&merged, &native_weights);
+                Self::sort_by_weights(merged, weights)
             }
+        };
+        response.results = self.postprocess(ranked, &options.postprocessors).await?;
+        response.query = query.clone();
+        response.bang = bang.clone();
+
+        Ok(response)
+    }
+
+    /// Apply the query's bang, then run the preprocessors
+    async fn prepare(
+        &self,
+        query: &mut Query,
+        providers: &mut Vec<String>,
+        options: &SearchOptions,
+    ) -> Result<Prepared, Error> {
+        let mut bang = None;
+
+        let found = bangs::split(&query.query, |trigger| self.resolve_bang(trigger, &options.bangs));
+        if let Some((trigger, target, rest)) = found {
+            match target {
+                BangTarget::Redirect(template) => match bangs::fill(&template, &rest) {
+                    Some(url) => {
+                        return Ok(Prepared {
+                            redirect: Some(url),
+                            bang: Some(trigger),
+                        });
+                    }
+                    None => warn!("bang !{trigger} has an unusable URL template {template:?}"),
+                },
+                BangTarget::Provider(provider) => {
+                    query.query = rest;
+                    *providers = vec![provider];
+                    bang = Some(trigger);
+                }
+            }
+        }
+
+        let redirect = self.preprocess(query, providers).await?;
+        Ok(Prepared { redirect, bang })
+    }
+
+    /// Complete a partly typed query
+    ///
+    /// Every suggester is asked at once, whatever they send within
+    /// `suggest.timeout` is merged by taking turns, without repeats.
+    /// Completions are cached by prefix.
+    pub async fn suggest(&self, mut query: Query) -> Vec<String> {
+        // A trailing space asks for the next word, so only the start is trimmed
+        query.query = query.query.trim_start().to_owned();
+        if query.query.is_empty() {
+            return Vec::new();
         }
+
+        let prefix = query.query.to_lowercase();
+        let (language, region) = (query.language.as_deref(), query.region.as_deref());
+        let ttl = self.suggest_cfg.cache_ttl;
+        let cached = (ttl > 0)
+            .then(|| self.suggestion_cache.get(&prefix, language, region, ttl))
+            .flatten();
+        if let Some(cached) = cached {
+            return cached;
+        }
+
+        let suggesters = match &self.suggest_cfg.suggesters {
+            Some(suggesters) => suggesters.clone(),
+            None => Self::registered(&*self.lua().await, "__searched_suggesters__")
+                .map(|registered| registered.into_iter().map(|(name, _)| name).collect())
+                .unwrap_or_default(),
+        };
+
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(self.suggest_cfg.timeout);
+        let mut set = JoinSet::new();
+        for (i, suggester) in suggesters.iter().enumerate() {
+            let eng = self.clone();
+            let query = query.clone();
+            let suggester = suggester.clone();
+            set.spawn(async move { (i, eng.suggest_single(query, &suggester).await) });
         }
+
+        // Keep batches in suggester order, so the preferred ones go first
+        let mut batches = vec![Vec::new(); suggesters.len()];
+        while let Ok(Some(joined)) = timeout_at(deadline, set.join_next()).await {
+            match joined {
+                Ok((i, Ok(batch))) => batches[i] = batch,
+                Ok((i, Err(err))) => debug!("suggester {} failed: {err}", suggesters[i]),
+                Err(err) => debug!("suggester task failed: {err}"),
+            }
+        }
+        // Whatever is still running is too late to be useful
+        set.abort_all();
+
+        let suggestions = Self::interleave(batches, self.suggest_cfg.limit);
+        // Failures aren't worth remembering
+        if ttl > 0 && !suggestions.is_empty() {
+            self.suggestion_cache
+                .insert(&prefix, language, region, &suggestions);
+        }
+
+        suggestions
+    }
+
+    /// Take completions from each batch in turn, leaving out repeats
+    fn interleave(batches: Vec<Vec<String>>, limit: usize) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut merged = Vec::new();
+
+        let longest = batches.iter().map(Vec::len).max().unwrap_or_default();
+        for i in 0..longest {
+            for suggestion in batches.iter().filter_map(|batch| batch.get(i)) {
+                let suggestion = suggestion.trim();
+                if !suggestion.is_empty() && seen.insert(suggestion.to_lowercase()) {
+                    merged.push(suggestion.to_owned());
+                }
+            }
+        }
+
+        merged.truncate(limit);
+        merged
+    }
+
+    /// Ask a single suggester for completions
+    async fn suggest_single(&self, query: Query, suggester: &str) -> Result<Vec<String>, Error> {
+        let lua = self.lua().await;
+        let suggester_impl = lua
+            .globals()
+            .get::<LuaTable>("__searched_suggesters__")?
+            .get::<Option<LuaFunction>>(suggester)?
+            .ok_or_else(|| Error::SuggesterNotFound(suggester.to_owned()))?;
+
+        let client = ClientWrapper {
+            transport: self.transport.for_provider(suggester),
+            accept_language: query.accept_language(),
+        };
+
+        call_limited(&lua, &suggester_impl, (client, query), self.call_limit).await
     }
 
-    pub async fn search(&self, query: Query, providers: Vec<String>) -> Result<Vec<SearchResult>, Error> {
-        let results = self.search_multi(query.clone(), providers).await?;
-        let merged = self.merge("multiprovider".to_owned(), query.clone(), results).await?;
-        let ranked = self.rank("multiprovider".to_owned(), query, merged).await?;
+    /// Find what a bang does
+    ///
+    /// The user's own bangs come first, then bangs.toml. Every provider is
+    /// also a bang for itself.
+    pub fn resolve_bang(&self, bang: &str, user_bangs: &HashMap<String, String>) -> Option<BangTarget> {
+        if let Some(template) = user_bangs.get(bang) {
+            return Some(BangTarget::Redirect(template.clone()));
+        }
+
+        if let Some(cfg) = self.bangs.0.get(bang) {
+            if let Some(url) = &cfg.url {
+                return Some(BangTarget::Redirect(url.clone()));
+            }
+            if let Some(provider) = &cfg.provider {
+                return Some(BangTarget::Provider(provider.clone()));
+            }
+        }
+
+        self.providers()
+            .0
+            .contains_key(bang)
+            .then(|| BangTarget::Provider(bang.to_owned()))
+    }
 
-        Ok(ranked)
+    /// Get the bangs from bangs.toml
+    pub fn bangs(&self) -> Arc<BangsConfig> {
+        self.bangs.clone()
     }
 
-    async fn merge(&self, merger: String, query: Query, results: Vec<SearchResult>) -> Result<Vec<SearchResult>, Error> {
-        let merger_impl = self.lua.globals().get::<LuaTable>("__searched_mergers__").unwrap().get::<LuaFunction>(merger).unwrap();
+    /// Run every preprocessor on the query, in order of their names
+    ///
+    /// Preprocessors can rewrite the query and pick which providers are
+    /// asked, or give a URL to redirect to instead of searching. One that
+    /// fails is skipped.
+    async fn preprocess(
+        &self,
+        query: &mut Query,
+        providers: &mut Vec<String>,
+    ) -> Result<Option<String>, Error> {
+        let lua = self.lua().await;
 
-        let results = merger_impl
-            .call_async::<Vec<LuaTable>>((
-                self.lua.to_value(&results).unwrap_or(LuaValue::Nil),
-                self.lua.create_table().unwrap(),
-            ))
+        for (name, preprocessor) in Self::registered(&lua, "__searched_preprocessors__")? {
+            let query_ud = lua.create_userdata(query.clone())?;
+            let res = async {
+                let out: LuaValue = call_limited(
+                    &lua,
+                    &preprocessor,
+                    (query_ud.clone(), lua.create_sequence_from(providers.iter().cloned())?),
+                    self.call_limit,
+                )
+                .await?;
+                let out: Option<Preprocessed> = lua.from_value(out)?;
+                let query = query_ud.borrow::<Query>()?.clone();
+                Ok::<_, Error>((query, out.unwrap_or_default()))
+            }
             .await;
 
-        match results {
-            Ok(results) => {
-                return Ok(results
-                    .into_iter()
-                    .filter_map(|r| {
-                        let res: SearchResult = self.lua.from_value(LuaValue::Table(r)).unwrap();
-                        if res.providers.is_empty() {
-                            None
-                        } else {
-                            Some(res)
-                        }
-                    })
-                    .collect());
+            match res {
+                Ok((new_query, out)) => {
+                    *query = new_query;
+                    if let Some(new_providers) = out.providers {
+                        *providers = new_providers;
+                    }
+                    if out.redirect.is_some() {
+                        debug!("preprocessor {name} redirected the search");
+                        return Ok(out.redirect);
+                    }
+                }
+                Err(err) => warn!("skipping preprocessor {name}: {err}"),
             }
-            Err(_) => {}
         }
 
-        Ok(Vec::new())
+        Ok(None)
     }
 
-    async fn rank(&self, ranker: String, query: Query, results: Vec<SearchResult>) -> Result<Vec<SearchResult>, Error> {
-        let ranker_impl = self.lua.globals().get::<LuaTable>("__searched_rankers__").unwrap().get::<LuaFunction>(ranker).unwrap();
+    /// Run every postprocessor on the ranked results, in order of their names
+    ///
+    /// Postprocessors can rewrite, drop or annotate results. Each gets its
+    /// entry in `options`, or an empty table. One that fails is skipped.
+    async fn postprocess(
+        &self,
+        mut results: Vec<SearchResult>,
+        options: &HashMap<String, HashMap<String, String>>,
+    ) -> Result<Vec<SearchResult>, Error> {
+        let lua = self.lua().await;
 
-        let weights = ranker_impl
-            .call_async::<Vec<LuaNumber>>((
-                self.lua.to_value(&results).unwrap_or(LuaValue::Nil),
-                self.lua.create_table().unwrap(),
-            ))
+        for (name, postprocessor) in Self::registered(&lua, "__searched_postprocessors__")? {
+            let res = async {
+                let out: Vec<LuaTable> = call_limited(
+                    &lua,
+                    &postprocessor,
+                    (
+                        lua.to_value(&results)?,
+                        lua.to_value(&options.get(&name).cloned().unwrap_or_default())?,
+                    ),
+                    self.call_limit,
+                )
+                .await?;
+                out.into_iter()
+                    .map(|r| Ok(lua.from_value(LuaValue::Table(r))?))
+                    .collect::<Result<Vec<SearchResult>, Error>>()
+            }
             .await;
 
-        match weights {
-            Ok(weights) => {
-                let mut res_weights = results
-                    .into_iter()
-                    .zip(weights)
-                    .collect::<Vec<_>>();
-                res_weights.sort_by_key(|r| (r.1 * 100.0) as u16);
-                res_weights.reverse();
-                return Ok(res_weights
-                    .into_iter()
-                    .unzip::<SearchResult, f64, Vec<_>, Vec<_>>()
-                    .0);
+            match res {
+                Ok(processed) => results = processed,
+                Err(err) => warn!("skipping postprocessor {name}: {err}"),
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Get everything registered in a registry, sorted by name
+    fn registered(lua: &Lua, registry: &str) -> Result<Vec<(String, LuaFunction)>, Error> {
+        let mut registered = lua
+            .globals()
+            .get::<LuaTable>(registry)?
+            .pairs::<String, LuaFunction>()
+            .collect::<LuaResult<Vec<_>>>()?;
+        registered.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(registered)
+    }
+
+    async fn merge(&self, merger: CfgStage, results: Vec<SearchResult>) -> Result<Vec<SearchResult>, Error> {
+        let lua = self.lua().await;
+        let merger_impl = lua
+            .globals()
+            .get::<LuaTable>("__searched_mergers__")?
+            .get::<Option<LuaFunction>>(merger.name.as_str())?;
+
+        // Lua mergers take precedence over the native one
+        let Some(merger_impl) = merger_impl else {
+            return match merger.name.as_str() {
+                merger::NAME => Ok(merger::merge(results)),
+                _ => Err(Error::MergerNotFound(merger.name)),
+            };
+        };
+
+        let results: Vec<LuaTable> = call_limited(
+            &lua,
+            &merger_impl,
+            (lua.to_value(&results)?, lua.to_value(&merger.options)?),
+            self.call_limit,
+        )
+        .await?;
+
+        let mut merged = Vec::with_capacity(results.len());
+        for r in results {
+            let res: SearchResult = lua.from_value(LuaValue::Table(r))?;
+            if !res.providers.is_empty() {
+                merged.push(res);
             }
-            Err(_) => {}
         }
 
-        Ok(Vec::new())
+        Ok(merged)
     }
 
+    async fn rank(&self, ranker: CfgStage, results: Vec<SearchResult>) -> Result<Vec<SearchResult>, Error> {
+        let lua = self.lua().await;
+        let ranker_impl = lua
+            .globals()
+            .get::<LuaTable>("__searched_rankers__")?
+            .get::<Option<LuaFunction>>(ranker.name.as_str())?;
+
+        // Lua rankers take precedence over the native one
+        let weights: Vec<LuaNumber> = match ranker_impl {
+            Some(ranker_impl) => {
+                call_limited(
+                    &lua,
+                    &ranker_impl,
+                    (lua.to_value(&results)?, lua.to_value(&ranker.options)?),
+                    self.call_limit,
+                )
+                .await?
+            }
+            None if ranker.name == ranker::NAME => ranker::rank(&results, &ranker.options),
+            None => return Err(Error::RankerNotFound(ranker.name)),
+        };
+
+        Ok(Self::sort_by_weights(results, weights))
+    }
+
+    /// Order results by the weights a ranker gave them, highest first
+    fn sort_by_weights(results: Vec<SearchResult>, weights: Vec<f64>) -> Vec<SearchResult> {
+        let mut res_weights = results
+            .into_iter()
+            .zip(weights)
+            .collect::<Vec<_>>();
+        // Sorting is stable, so ties keep the order they were merged in
+        res_weights.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        res_weights
+            .into_iter()
+            .unzip::<SearchResult, f64, Vec<_>, Vec<_>>()
+            .0
+    }
+
+    /// Run the query on every provider at once
+    ///
+    /// Each provider gets its own timeout, and the whole search gets a
+    /// deadline. Whatever has answered by then is returned along with the
+    /// providers that didn't make it. Suspended providers are skipped.
+    ///
+    /// With `progress`, everything returned so far is also sent there every
+    /// time a provider answers or fails.
     async fn search_multi(
         &self,
         query: Query,
-        providers: Vec<String>,
-    ) -> Result<Vec<crate::SearchResult>, Error> {
+        mut providers: Vec<String>,
+        options: &SearchOptions,
+        progress: Option<mpsc::UnboundedSender<SearchResponse>>,
+    ) -> Result<SearchResponse, Error> {
+        let deadline =
+            tokio::time::Instant::now() + Duration::from_millis(self.search_cfg.deadline);
+        let provider_cfgs = self.providers();
+
+        // Providers that can't paginate only have a first page
+        if query.page > 1 {
+            providers.retain(|provider| {
+                let paginates = provider_cfgs
+                    .0
+                    .get(provider)
+                    .and_then(|cfg| cfg.features.as_ref())
+                    .is_some_and(|f| f.pagination != CfgPaginationSupport::No);
+                if !paginates {
+                    debug!("skipping provider {provider}, it can't go past the first page");
+                }
+                paginates
+            });
+        }
+
+        // Last, as this lets a suspended provider's trial request through
+        let (suspended, providers): (Vec<_>, Vec<_>) = providers
+            .into_iter()
+            .partition(|provider| !self.health.admit(provider));
+
         let mut set = JoinSet::new();
+        let mut pending = HashMap::new();
 
-        for provider in providers {
+        for (i, provider) in providers.iter().enumerate() {
             let eng = self.clone();
             let query = query.clone();
             let provider = provider.clone();
+            let provider_cfg = provider_cfgs.0.get(&provider);
+            let timeout = Duration::from_millis(
+                provider_cfg
+                    .and_then(|p| p.timeout)
+                    .unwrap_or(self.search_cfg.provider_timeout),
+            );
+            let cache_ttl = provider_cfg
+                .and_then(|p| p.cache_ttl)
+                .unwrap_or(self.search_cfg.cache_ttl);
+            let extra = provider_cfg
+                .map(|cfg| Self::user_extra(cfg, options.providers.get(&provider)))
+                .unwrap_or_default();
 
-            set.spawn(async move {
-                match Self::search_single(&eng, query, &provider).await {
-                    Ok(res) => res,
-                    Err(_) => Vec::new(),
-                }
+            let handle = set.spawn(async move {
+                let start = tokio::time::Instant::now();
+                let res = tokio::time::timeout(timeout, eng.search_cached(query, provider, extra, cache_ttl)).await;
+                (i, res, start.elapsed())
             });
+            pending.insert(handle.id(), i);
         }
 
-        //let mut results: HashMap<Url, Vec<SearchResult>> = HashMap::new();
+        // Keep batches in provider order so ranking ties don't depend on timing
+        let mut answers = vec![ProviderResponse::default(); providers.len()];
+        let mut errors = Vec::new();
+        let mut timings = HashMap::new();
+
+        while !pending.is_empty() {
+            match timeout_at(deadline, set.join_next_with_id()).await {
+                Ok(Some(Ok((id, (i, res, took))))) => {
+                    pending.remove(&id);
+                    timings.insert(providers[i].clone(), took.as_millis() as u64);
+                    match res.unwrap_or(Err(Error::Timeout)) {
+                        Ok(answer) => answers[i] = answer,
+                        Err(err) => errors.push((i, err)),
+                    }
+                }
+                Ok(Some(Err(err))) => {
+                    if let Some(i) = pending.remove(&err.id()) {
+                        errors.push((i, Error::Lua(err.to_string())));
+                    }
+                }
+                Ok(None) => break,
+                Err(_) => {
+                    warn!("search deadline hit with {} providers pending", pending.len());
+                    errors.extend(pending.drain().map(|(_, i)| (i, Error::Timeout)));
+                }
+            }
 
-        let result_batches = set.join_all().await;
+            if let Some(progress) = &progress {
+                errors.sort_unstable_by_key(|(i, _)| *i);
+                let mut update = SearchResponse {
+                    results: answers.iter().flat_map(|a| a.results.clone()).collect(),
+                    queried: providers.clone(),
+                    timings: timings.clone(),
+                    errors: errors
+                        .iter()
+                        .map(|(i, error)| ProviderError {
+                            provider: providers[*i].clone(),
+                            error: error.clone(),
+                        })
+                        .collect(),
+                    suspended: suspended.clone(),
+                    pending: providers
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| pending.values().any(|p| p == i))
+                        .map(|(_, provider)| provider.clone())
+                        .collect(),
+                    ..Default::default()
+                };
+                meta::combine(&query.query, &answers, &mut update);
+                let _ = progress.send(update);
+            }
+        }
 
-        //for (batch, provider) in result_batches {
-        //    for res in batch {
-        //        if let Some(results) = results.get_mut(&res.url) {
-        //            results.push(res);
-        //        } else {
-        //            results.insert(res.url, vec![res.clone()]);
-        //        }
-        //    }
-        //}
+        // Stop anything still running past the deadline
+        set.abort_all();
 
-        //let mut results = results.into_iter().collect::<Vec<_>>();
-        //results.sort_by_key(|r| r.1.len());
-        //results.reverse();
+        errors.sort_unstable_by_key(|(i, _)| *i);
+        for (i, err) in &errors {
+            let target = format!("searched::provider::{}", providers[*i]);
+            error!(target: &target, "failed to get results from provider {}: {err}", providers[*i]);
+            self.health.record_failure(&providers[*i], err);
+        }
 
-        //let mut results = results.iter().map(|(r, _)| r.clone()).collect::<Vec<_>>();
-        //results.dedup_by_key(|r| r.url.clone());
+        let mut response = SearchResponse {
+            results: answers.iter().flat_map(|a| a.results.clone()).collect(),
+            errors: errors
+                .into_iter()
+                .map(|(i, error)| ProviderError {
+                    provider: providers[i].clone(),
+                    error,
+                })
+                .collect(),
+            suspended,
+            queried: providers,
+            timings,
+            ..Default::default()
+        };
+        meta::combine(&query.query, &answers, &mut response);
 
-        Ok(result_batches.concat())
+        Ok(response)
     }
 
-    /// Process the given query
+    /// Process the given query, answering from the response cache when
+    /// the provider was asked the same thing less than `cache_ttl` seconds ago
+    async fn search_cached(
+        &self,
+        query: Query,
+        provider: String,
+        extra: BTreeMap<String, String>,
+        cache_ttl: u64,
+    ) -> Result<ProviderResponse, Error> {
+        if cache_ttl == 0 {
+            return self.search_single(query, provider, &extra).await;
+        }
+
+        let cached = (!query.no_cache)
+            .then(|| self.cache.get(&provider, &query, &extra, cache_ttl))
+            .flatten();
+        if let Some(response) = cached {
+            debug!("using cached results from provider {provider}");
+            return Ok(response);
+        }
+
+        let response = self.search_single(query.clone(), &provider, &extra).await?;
+
+        // Empty responses are usually a failed scrape, don't keep those around
+        if !response.results.is_empty() {
+            self.cache.insert(&provider, &query, &extra, &response);
+        }
+
+        Ok(response)
+    }
+
+    /// Adapt a query to what a provider supports
+    ///
+    /// Pages are counted from the provider's first page, and safe search
+    /// levels it can't tell apart are mapped to the closest one it has.
+    fn engine_query(mut query: Query, features: &CfgProviderFeatures) -> Query {
+        if features.pagination == CfgPaginationSupport::StartAt0 {
+            query.page = query.page.saturating_sub(1);
+        }
+
+        query.safe = match (features.safe_search, query.safe) {
+            (CfgSafeSearchSupport::MultiLevel, safe) => safe,
+            // Err on the side of filtering when there's only on and off
+            (CfgSafeSearchSupport::Yes, SafeSearch::Off) => SafeSearch::Off,
+            (CfgSafeSearchSupport::Yes, _) => SafeSearch::Strict,
+            (CfgSafeSearchSupport::No, _) => SafeSearch::Off,
+        };
+
+        query
+    }
+
+    /// Run one provider's engine, skipping the cache and everything done to
+    /// results after
+    pub async fn search_provider(&self, query: Query, provider: &str) -> Result<ProviderResponse, Error> {
+        self.search_single(query, provider, &BTreeMap::new()).await
+    }
+
+    /// Process the given query, with `extra` replacing engine options
     async fn search_single(
         &self,
         query: Query,
         provider: impl Into<String>,
-    ) -> Result<Vec<crate::SearchResult>, Error> {
+        extra: &BTreeMap<String, String>,
+    ) -> Result<ProviderResponse, Error> {
         let provider = provider.into();
+        // Later pages can run out, a first page shouldn't be empty
+        let expects_results = query.page == 1 && !query.query.trim().is_empty();
 
-        #[cfg(feature = "hot_reload")]
-        Self::load_plugins(&self.lua).await;
+        let lua = self.lua().await;
+        let providers = self.providers();
 
-        #[cfg(feature = "hot_reload")]
-        let providers = ProvidersConfig::load("plugins/providers.toml");
-        #[cfg(not(feature = "hot_reload"))]
-        let providers = &self.providers;
-
-        if let Some(p) = providers.0.get(&provider) {
-            let providers = vec![provider.clone()];
-
-            // If the provider has an engine specified, use that engine.
-            // Otherwise, the provider is also an engine
-            let engine = p.engine.clone().unwrap_or_else(|| provider.clone());
-            let target = format!("searched::engine::{engine}");
-
-            // Get engine implementation
-            let eng_impl = self
-                .lua
-                .globals()
-                .get::<LuaTable>("__searched_engines__")
-                .unwrap()
-                .get::<LuaFunction>(engine)
-                .map_err(|_| Error::EngineNotLoaded)?;
-
-            // Run engine for query
-            let results = eng_impl
-                .call_async::<Vec<LuaTable>>((
-                    ClientWrapper(self.client.clone()),
-                    query.clone(),
-                    self.lua.to_value(&p.clone().extra.unwrap_or_default()),
-                ))
-                .await;
-
-            match results {
-                Ok(results) => {
-                    return Ok(results
-                        .into_iter()
-                        .map(|r| {
-                            let mut result: SearchResult = self.lua.from_value(LuaValue::Table(r)).unwrap();
-                            result.providers = providers.clone();
-                            result
-                        })
-                        .collect());
-                }
-                Err(err) => {
-                    error!(target: &target, "failed to get results from provider {provider}: {err}");
-                }
-            }
+        let p = providers
+            .0
+            .get(&provider)
+            .ok_or_else(|| Error::ProviderNotFound(provider.clone()))?;
+
+        // If the provider has an engine specified, use that engine.
+        // Otherwise, the provider is also an engine
+        let engine = p.engine.clone().unwrap_or_else(|| provider.clone());
+
+        // Get engine implementation
+        let eng_impl = lua
+            .globals()
+            .get::<LuaTable>("__searched_engines__")?
+            .get::<Option<LuaFunction>>(engine.as_str())?
+            .ok_or(Error::EngineNotLoaded(engine))?;
+
+        let mut options = p.extra.clone().unwrap_or_default();
+        for (key, value) in extra {
+            options.insert(key.clone(), toml::Value::String(value.clone()));
+        }
+
+        let engine_query = Self::engine_query(query, &p.features.clone().unwrap_or_default());
+        let client = ClientWrapper {
+            transport: self.transport.for_provider(&provider),
+            accept_language: engine_query.accept_language(),
+        };
+
+        // Run engine for query
+        let start = Instant::now();
+        let output: LuaTable = call_limited(
+            &lua,
+            &eng_impl,
+            (
+                client,
+                engine_query,
+                lua.to_value(&options)?,
+            ),
+            self.call_limit,
+        )
+        .await?;
+
+        // Engines with more to say than results put them under `results`
+        let (results, response) = match output.get::<Option<LuaTable>>("results")? {
+            Some(results) => (
+                results,
+                ProviderResponse {
+                    correction: output
+                        .get::<Option<String>>("correction")?
+                        .filter(|c| !c.trim().is_empty()),
+                    related: output.get::<Option<Vec<String>>>("related")?.unwrap_or_default(),
+                    total: output.get("total")?,
+                    ..Default::default()
+                },
+            ),
+            None => (output, ProviderResponse::default()),
+        };
+
+        let results = lua
+            .unpack::<Vec<LuaTable>>(LuaValue::Table(results))?
+            .into_iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let mut result: SearchResult = lua.from_value(LuaValue::Table(r))?;
+                result.providers = vec![provider.clone()];
+                result.positions = BTreeMap::from([(provider.clone(), i + 1)]);
+                result.date = recency::normalize(result.date);
+                Ok(result)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if results.is_empty() && expects_results {
+            self.health.record_empty(&provider);
+        } else {
+            self.health.record_success(&provider, start.elapsed());
         }
 
-        Ok(Vec::new())
+        Ok(ProviderResponse { results, ..response })
     }
 }
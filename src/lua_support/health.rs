@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::config::CfgSearch;
+
+/// How long a provider can take to answer the request that checks whether
+/// it's back, before another request gets to try
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Health of a single provider
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ProviderHealth {
+    pub successes: u64,
+    pub failures: u64,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    /// Latency of the last successful request, in milliseconds
+    pub last_latency: Option<u64>,
+    /// Moving average of successful request latency, in milliseconds
+    pub avg_latency: Option<f64>,
+    pub last_error: Option<String>,
+    /// Seconds left until the provider is tried again, if suspended
+    pub suspended_for: Option<u64>,
+    #[serde(skip)]
+    suspended_until: Option<Instant>,
+    /// When the request trying a provider again after its suspension started
+    #[serde(skip)]
+    probing_since: Option<Instant>,
+}
+impl ProviderHealth {
+    /// Whether the provider is currently being skipped
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_until
+            .is_some_and(|until| until > Instant::now())
+    }
+
+    /// Share of requests that succeeded, if there were any
+    pub fn success_rate(&self) -> Option<f64> {
+        let total = self.successes + self.failures;
+        (total > 0).then(|| self.successes as f64 / total as f64)
+    }
+}
+
+/// Tracks provider health and suspends failing providers
+///
+/// After `failure_threshold` consecutive failures a provider is suspended
+/// for `backoff_base` seconds, doubling for every further failure up to
+/// `backoff_max`. Once a suspension runs out a single request gets to try
+/// the provider again, and the provider stays suspended for everyone else
+/// until it's done.
+///
+/// Answering without any results to a query that should have some counts
+/// as a failure too, as blocked scrapers tend to come back empty.
+#[derive(Clone)]
+pub(super) struct HealthTracker {
+    providers: Arc<RwLock<HashMap<String, ProviderHealth>>>,
+    failure_threshold: u32,
+    backoff_base: u64,
+    backoff_max: u64,
+}
+impl HealthTracker {
+    pub fn new(cfg: &CfgSearch) -> Self {
+        Self {
+            providers: Arc::new(RwLock::new(HashMap::new())),
+            failure_threshold: cfg.failure_threshold.max(1),
+            backoff_base: cfg.backoff_base,
+            backoff_max: cfg.backoff_max,
+        }
+    }
+
+    /// Whether a search may ask a provider
+    ///
+    /// Once a suspension has run out, only the first caller is let through
+    /// until its request succeeds or fails.
+    pub fn admit(&self, provider: &str) -> bool {
+        let mut providers = self.providers.write().unwrap();
+        let Some(health) = providers.get_mut(provider) else {
+            return true;
+        };
+
+        let now = Instant::now();
+        match health.suspended_until {
+            None => true,
+            Some(until) if until > now => false,
+            Some(_) => {
+                let probing = health
+                    .probing_since
+                    .is_some_and(|since| now - since < PROBE_TIMEOUT);
+                if !probing {
+                    health.probing_since = Some(now);
+                }
+                !probing
+            }
+        }
+    }
+
+    pub fn record_success(&self, provider: &str, latency: Duration) {
+        let mut providers = self.providers.write().unwrap();
+        let health = providers.entry(provider.to_owned()).or_default();
+
+        let latency = latency.as_millis() as u64;
+        health.successes += 1;
+        health.consecutive_failures = 0;
+        health.last_latency = Some(latency);
+        health.avg_latency = Some(match health.avg_latency {
+            Some(avg) => avg * 0.8 + latency as f64 * 0.2,
+            None => latency as f64,
+        });
+        health.suspended_until = None;
+        health.probing_since = None;
+    }
+
+    /// Record an answer without results to a query that should have some
+    pub fn record_empty(&self, provider: &str) {
+        self.record_failure(provider, "no results");
+    }
+
+    pub fn record_failure(&self, provider: &str, err: impl ToString) {
+        let mut providers = self.providers.write().unwrap();
+        let health = providers.entry(provider.to_owned()).or_default();
+
+        health.failures += 1;
+        health.consecutive_failures += 1;
+        health.last_error = Some(err.to_string());
+        health.probing_since = None;
+
+        if health.consecutive_failures >= self.failure_threshold {
+            let exp = (health.consecutive_failures - self.failure_threshold).min(16);
+            let backoff = self
+                .backoff_base
+                .saturating_mul(1 << exp)
+                .min(self.backoff_max);
+
+            warn!(
+                "suspending provider {provider} for {backoff}s after {} failures",
+                health.consecutive_failures
+            );
+            health.suspended_until = Some(Instant::now() + Duration::from_secs(backoff));
+        }
+    }
+
+    /// Get a snapshot of every provider's health
+    pub fn snapshot(&self) -> HashMap<String, ProviderHealth> {
+        let now = Instant::now();
+        self.providers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, health)| {
+                let mut health = health.clone();
+                health.suspended_for = health
+                    .suspended_until
+                    .filter(|until| *until > now)
+                    .map(|until| (until - now).as_secs());
+                (name.clone(), health)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tracker that suspends after two failures, for no time at all
+    fn tracker() -> HealthTracker {
+        HealthTracker::new(&CfgSearch {
+            failure_threshold: 2,
+            backoff_base: 0,
+            backoff_max: 0,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn suspends_after_threshold() {
+        let health = HealthTracker::new(&CfgSearch {
+            failure_threshold: 2,
+            ..Default::default()
+        });
+        health.record_failure("a", "oops");
+        assert!(health.admit("a"));
+        health.record_empty("a");
+        assert!(!health.admit("a"));
+        assert!(health.admit("b"));
+    }
+
+    #[test]
+    fn single_probe_after_suspension() {
+        let health = tracker();
+        health.record_failure("a", "oops");
+        health.record_failure("a", "oops");
+
+        // The suspension is over, but only one request gets to try
+        assert!(health.admit("a"));
+        assert!(!health.admit("a"));
+
+        // A failed probe suspends again, and the next one gets a try
+        health.record_failure("a", "oops");
+        assert!(health.admit("a"));
+        assert!(!health.admit("a"));
+
+        health.record_success("a", Duration::from_millis(10));
+        assert!(health.admit("a"));
+        assert!(health.admit("a"));
+    }
+}
@@ -0,0 +1,188 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use mlua::prelude::*;
+
+use crate::Error;
+
+/// How often the time budget is checked, in Lua instructions
+#[cfg(not(feature = "__luau-jit"))]
+const CHECK_INTERVAL: u32 = 1000;
+
+/// When the call running on a Lua state runs out of time
+///
+/// Only set while the call is being polled.
+struct Deadline {
+    at: Instant,
+    limit: Duration,
+}
+
+/// Call a plugin function, failing if it runs for longer than `limit`
+///
+/// Only time spent running counts, so waiting on the network doesn't eat
+/// into the budget. A zero limit disables the check.
+///
+/// The call needs the Lua state to itself, as there's only one hook per
+/// state. [LuaPool](super::pool::LuaPool) takes care of that.
+pub(super) async fn call_limited<R: FromLuaMulti>(
+    lua: &Lua,
+    func: &LuaFunction,
+    args: impl IntoLuaMulti,
+    limit: Duration,
+) -> Result<R, Error> {
+    if limit.is_zero() {
+        return Ok(func.call_async(args).await?);
+    }
+
+    let thread = lua.create_thread(func.clone())?;
+    let call = Limited {
+        lua: lua.clone(),
+        #[cfg(not(feature = "__luau-jit"))]
+        thread: thread.clone(),
+        call: Box::pin(thread.into_async::<R>(args)),
+        spent: Duration::ZERO,
+        limit,
+    };
+
+    Ok(call.await?)
+}
+
+/// Make coroutines keep to the time budget of the call that runs them
+///
+/// Lua hooks are per thread, so `coroutine.resume` moves ours onto the
+/// coroutine and back. `coroutine.wrap` is rebuilt on top of it.
+#[cfg(not(feature = "__luau-jit"))]
+pub(super) fn limit_coroutines(lua: &Lua) -> LuaResult<()> {
+    let Some(coroutine) = lua.globals().get::<Option<LuaTable>>("coroutine")? else {
+        return Ok(());
+    };
+
+    let resume = coroutine.get::<LuaFunction>("resume")?;
+    coroutine.set(
+        "resume",
+        lua.create_function(move |lua, (co, args): (LuaThread, LuaMultiValue)| {
+            let limited = lua.app_data_ref::<Deadline>().is_some();
+            if limited {
+                watch(&co);
+            }
+            let res = resume.call::<LuaMultiValue>((co, args));
+            if limited {
+                watch(&lua.current_thread());
+            }
+            res
+        })?,
+    )?;
+
+    lua.load(
+        r#"
+        local create, resume = coroutine.create, coroutine.resume
+        local pack, unpack = table.pack, table.unpack
+        function coroutine.wrap(f)
+            local co = create(f)
+            return function(...)
+                local res = pack(resume(co, ...))
+                if not res[1] then
+                    error(res[2], 0)
+                end
+                return unpack(res, 2, res.n)
+            end
+        end
+        "#,
+    )
+    .set_name("=coroutine")
+    .exec()
+}
+
+/// Check the time budget on a thread while it runs
+#[cfg(not(feature = "__luau-jit"))]
+fn watch(thread: &LuaThread) {
+    thread.set_hook(
+        LuaHookTriggers::new().every_nth_instruction(CHECK_INTERVAL),
+        |lua, _| check(lua),
+    );
+}
+
+fn check(lua: &Lua) -> LuaResult<LuaVmState> {
+    match lua.app_data_ref::<Deadline>() {
+        Some(deadline) if Instant::now() > deadline.at => Err(LuaError::external(
+            Error::LimitExceeded(format!("time budget of {}ms", deadline.limit.as_millis())),
+        )),
+        _ => Ok(LuaVmState::Continue),
+    }
+}
+
+/// A plugin call that keeps track of how long it has been running
+struct Limited<F> {
+    lua: Lua,
+    #[cfg(not(feature = "__luau-jit"))]
+    thread: LuaThread,
+    call: Pin<Box<F>>,
+    spent: Duration,
+    limit: Duration,
+}
+impl<F: Future> Future for Limited<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let start = Instant::now();
+        self.lua.set_app_data(Deadline {
+            at: start + self.limit.saturating_sub(self.spent),
+            limit: self.limit,
+        });
+
+        #[cfg(not(feature = "__luau-jit"))]
+        watch(&self.thread);
+        #[cfg(feature = "__luau-jit")]
+        self.lua.set_interrupt(check);
+
+        let res = self.call.as_mut().poll(cx);
+
+        #[cfg(not(feature = "__luau-jit"))]
+        self.lua.remove_hook();
+        #[cfg(feature = "__luau-jit")]
+        self.lua.remove_interrupt();
+        self.lua.remove_app_data::<Deadline>();
+
+        self.spent += start.elapsed();
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run(src: &str) -> Result<(), Error> {
+        let lua = Lua::new();
+        #[cfg(not(feature = "__luau-jit"))]
+        limit_coroutines(&lua).unwrap();
+        let func = lua.load(src).into_function().unwrap();
+        call_limited(&lua, &func, (), Duration::from_millis(50)).await
+    }
+
+    #[tokio::test]
+    async fn stops_loops() {
+        let res = run("while true do end").await;
+        assert!(matches!(res, Err(Error::LimitExceeded(_))), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn stops_loops_in_coroutines() {
+        // resume may catch the error, what matters is that the loop stops
+        let res = run("coroutine.resume(coroutine.create(function() while true do end end))").await;
+        assert!(matches!(res, Ok(()) | Err(Error::LimitExceeded(_))), "{res:?}");
+
+        let res = run("coroutine.wrap(function() while true do end end)()").await;
+        assert!(matches!(res, Err(Error::LimitExceeded(_))), "{res:?}");
+    }
+
+    #[tokio::test]
+    async fn lets_quick_calls_finish() {
+        let res = run("local co = coroutine.wrap(function() coroutine.yield(1) end) co()").await;
+        assert!(res.is_ok(), "{res:?}");
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use url::Url;
+
+use crate::{GeneralResult, SearchResult};
+
+/// Name the native merger is used by
+///
+/// A Lua merger registered under the same name replaces it.
+pub(super) const NAME: &str = "canonical";
+
+/// Host prefixes of mobile and AMP versions of sites
+const VARIANT_PREFIXES: &[&str] = &["www.", "m.", "mobile.", "amp."];
+
+/// Merge results that point to the same page
+///
+/// URLs are compared by their canonical form, so `http`/`https`, `www.`,
+/// mobile and AMP variants and trailing slashes don't matter. Results from
+/// the same site with nearly the same title are merged too. Merged results
+/// keep every provider, the richest snippet and the nicest URL.
+pub(super) fn merge(results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let mut merged: Vec<SearchResult> = Vec::with_capacity(results.len());
+    // Canonical host and normalized title of every merged result
+    let mut seen: Vec<(String, Vec<char>)> = Vec::with_capacity(results.len());
+    let mut by_url = HashMap::new();
+
+    for res in results {
+        let key = canonical_url(&res.url);
+        let host = canonical_host(&res.url).to_owned();
+        let title = normalize_title(&res.title);
+
+        let dup = by_url.get(&key).copied().or_else(|| {
+            seen.iter().position(|(other_host, other_title)| {
+                *other_host == host && similar_titles(other_title, &title)
+            })
+        });
+
+        match dup {
+            Some(i) => {
+                // Later variants of this URL belong here too
+                by_url.entry(key).or_insert(i);
+                absorb(&mut merged[i], res);
+            }
+            None => {
+                by_url.insert(key, merged.len());
+                merged.push(res);
+                seen.push((host, title));
+            }
+        }
+    }
+
+    merged
+}
+
+/// Merge a duplicate into the result it duplicates
+fn absorb(into: &mut SearchResult, dup: SearchResult) {
+    for provider in dup.providers {
+        if !into.providers.contains(&provider) {
+            into.providers.push(provider);
+        }
+    }
+    // A provider can list several variants, the best placed one counts
+    for (provider, pos) in dup.positions {
+        into.positions
+            .entry(provider)
+            .and_modify(|p| *p = (*p).min(pos))
+            .or_insert(pos);
+    }
+
+    if url_quality(&dup.url) > url_quality(&into.url) {
+        into.url = dup.url;
+    }
+
+    let snippet_len = |general: &Option<GeneralResult>| {
+        general
+            .as_ref()
+            .and_then(|g| g.snippet.as_ref())
+            .map_or(0, |s| s.trim().len())
+    };
+    if snippet_len(&dup.general) > snippet_len(&into.general) {
+        into.general = dup.general;
+    }
+
+    if into.forum.is_none() {
+        into.forum = dup.forum;
+    }
+    if into.image.is_none() {
+        into.image = dup.image;
+    }
+    for annotation in dup.annotations {
+        if !into.annotations.contains(&annotation) {
+            into.annotations.push(annotation);
+        }
+    }
+}
+
+/// Host without `www.`, mobile or AMP prefixes
+///
+/// A prefix only goes if a domain is left after it, `mobile.de` is a site
+/// of its own.
+fn canonical_host(url: &Url) -> &str {
+    let mut host = url.host_str().unwrap_or_default();
+    while let Some(rest) = VARIANT_PREFIXES
+        .iter()
+        .find_map(|prefix| host.strip_prefix(prefix))
+        .filter(|rest| rest.contains('.'))
+    {
+        host = rest;
+    }
+    host
+}
+
+/// Whether the path is that of an AMP version, like `/amp/some-article`
+///
+/// Only a leading `amp` counts, elsewhere it's as likely to be part of the
+/// page's own path.
+fn is_amp_path(url: &Url) -> bool {
+    url.path_segments()
+        .and_then(|mut segments| segments.next())
+        .is_some_and(|first| first == "amp")
+}
+
+/// The URL with everything that doesn't change the page it points to removed
+fn canonical_url(url: &Url) -> String {
+    // Google's AMP cache wraps the real URL
+    let amp_inner = url
+        .host_str()
+        .filter(|host| host.starts_with("www.google."))
+        .and_then(|_| url.path().strip_prefix("/amp/s/"))
+        .and_then(|inner| Url::parse(&format!("https://{inner}")).ok());
+    if let Some(inner) = amp_inner {
+        return canonical_url(&inner);
+    }
+
+    let segments = url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .skip(usize::from(is_amp_path(url)))
+                .filter(|seg| !seg.is_empty())
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .unwrap_or_default();
+    let segments = segments.strip_suffix(".amp").unwrap_or(&segments);
+
+    let params = url
+        .query_pairs()
+        .filter(|(k, v)| k != "amp" && !(k == "outputType" && v == "amp"))
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>();
+
+    let mut canonical = canonical_host(url).to_owned();
+    if let Some(port) = url.port() {
+        canonical.push_str(&format!(":{port}"));
+    }
+    canonical.push('/');
+    canonical.push_str(segments);
+    if !params.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&params.join("&"));
+    }
+    canonical
+}
+
+/// How much we'd rather show a URL than its variants
+fn url_quality(url: &Url) -> u8 {
+    let host = url.host_str().unwrap_or_default();
+    let is_variant = VARIANT_PREFIXES[1..]
+        .iter()
+        .any(|prefix| host.starts_with(prefix))
+        || is_amp_path(url);
+
+    u8::from(!is_variant) * 2 + u8::from(url.scheme() == "https")
+}
+
+/// Titles are lowercased and stripped to letters, numbers and single spaces
+fn normalize_title(title: &str) -> Vec<char> {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect()
+}
+
+/// Whether two normalized titles differ by no more than a few typos
+///
+/// Numbers have to match exactly, "Part 1" and "Part 2" are different pages.
+fn similar_titles(a: &[char], b: &[char]) -> bool {
+    if a == b {
+        return !a.is_empty();
+    }
+
+    let digits = |s: &[char]| s.iter().filter(|c| c.is_numeric()).collect::<String>();
+    let longest = a.len().max(b.len());
+    if longest < 24 || digits(a) != digits(b) {
+        return false;
+    }
+
+    edit_distance(a, b) * 20 <= longest
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            cur[j + 1] = (prev[j] + usize::from(ca != cb))
+                .min(prev[j + 1] + 1)
+                .min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(url: &str, title: &str, provider: &str) -> SearchResult {
+        SearchResult {
+            url: Url::parse(url).unwrap(),
+            title: title.to_owned(),
+            providers: vec![provider.to_owned()],
+            general: None,
+            forum: None,
+            image: None,
+            date: None,
+            positions: [(provider.to_owned(), 1)].into(),
+            annotations: Vec::new(),
+        }
+    }
+
+    fn host(url: &str) -> String {
+        canonical_host(&Url::parse(url).unwrap()).to_owned()
+    }
+
+    fn canonical(url: &str) -> String {
+        canonical_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn strips_variant_prefixes() {
+        assert_eq!(host("https://www.example.com/"), "example.com");
+        assert_eq!(host("https://m.example.com/"), "example.com");
+        assert_eq!(host("https://www.m.example.co.uk/"), "example.co.uk");
+        assert_eq!(host("https://amp.example.com/"), "example.com");
+    }
+
+    #[test]
+    fn keeps_a_domain() {
+        assert_eq!(host("https://mobile.de/"), "mobile.de");
+        assert_eq!(host("https://www.mobile.de/"), "mobile.de");
+        assert_eq!(host("https://m.de/"), "m.de");
+        assert_eq!(host("http://localhost/"), "localhost");
+    }
+
+    #[test]
+    fn canonical_urls() {
+        assert_eq!(canonical("http://www.example.com/a/b/"), "example.com/a/b");
+        assert_eq!(canonical("https://example.com/a/b"), "example.com/a/b");
+        assert_eq!(canonical("https://example.com/amp/a"), "example.com/a");
+        assert_eq!(canonical("https://example.com/a.amp"), "example.com/a");
+        assert_eq!(canonical("https://example.com/a?amp=1"), "example.com/a");
+        assert_eq!(
+            canonical("https://www.google.com/amp/s/example.com/a"),
+            "example.com/a"
+        );
+    }
+
+    #[test]
+    fn keeps_amp_inside_paths() {
+        assert_eq!(canonical("https://example.com/guitar/amp"), "example.com/guitar/amp");
+        assert_eq!(canonical("https://example.com/amp/guitar/amp"), "example.com/guitar/amp");
+        assert_ne!(canonical("https://example.com/guitar/amp"), canonical("https://example.com/guitar"));
+    }
+
+    #[test]
+    fn merges_variants() {
+        let merged = merge(vec![
+            result("http://m.example.com/page/", "Page", "a"),
+            result("https://www.example.com/page", "Page", "b"),
+            result("https://example.com/amp/page", "Page", "c"),
+        ]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].url.as_str(), "https://www.example.com/page");
+        assert_eq!(merged[0].providers, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn keeps_different_pages() {
+        let merged = merge(vec![
+            result("https://example.com/guitar", "Guitars", "a"),
+            result("https://example.com/guitar/amp", "Guitar amps", "b"),
+            result("https://mobile.de/", "Mobile", "c"),
+            result("https://de.example.com/", "Example", "d"),
+        ]);
+
+        assert_eq!(merged.len(), 4);
+    }
+
+    #[test]
+    fn title_matches_register_their_url() {
+        let title = "A rather long title about the same page";
+        let merged = merge(vec![
+            result("https://example.com/a", title, "a"),
+            // Same site and title, different URL
+            result("https://example.com/b", title, "b"),
+            // Same URL as the second, different title
+            result("https://example.com/b/", "Something else entirely", "c"),
+        ]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].providers, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn similar_titles_need_matching_numbers() {
+        let a = normalize_title("The Rust Programming Language, Part 1");
+        let b = normalize_title("The Rust Programing Language, Part 1");
+        let c = normalize_title("The Rust Programming Language, Part 2");
+
+        assert!(similar_titles(&a, &b));
+        assert!(!similar_titles(&a, &c));
+        assert!(!similar_titles(&[], &[]));
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use crate::{ProviderResponse, SearchResponse};
+
+/// Most related searches shown
+const MAX_RELATED: usize = 8;
+
+/// Gather what providers said about a search besides their results
+///
+/// Corrections go by how many providers agree on them, ties in provider
+/// order. Related searches take turns, and the total is the largest
+/// estimate, as no two providers count the same way. Anything that's just
+/// the query again is left out.
+pub(super) fn combine(query: &str, answers: &[ProviderResponse], response: &mut SearchResponse) {
+    let query = normalize(query);
+
+    let mut corrections: Vec<(String, String, usize)> = Vec::new();
+    for correction in answers.iter().filter_map(|answer| answer.correction.as_deref()) {
+        let key = normalize(correction);
+        if key.is_empty() || key == query {
+            continue;
+        }
+        match corrections.iter_mut().find(|(seen, _, _)| *seen == key) {
+            Some((_, _, votes)) => *votes += 1,
+            None => corrections.push((key, correction.trim().to_owned(), 1)),
+        }
+    }
+    // Stable, so ties keep provider order
+    corrections.sort_by_key(|(_, _, votes)| std::cmp::Reverse(*votes));
+    response.corrections = corrections.into_iter().map(|(_, correction, _)| correction).collect();
+
+    let mut seen = HashSet::from([query]);
+    let mut related = Vec::new();
+    let longest = answers.iter().map(|answer| answer.related.len()).max().unwrap_or_default();
+    for i in 0..longest {
+        for search in answers.iter().filter_map(|answer| answer.related.get(i)) {
+            let key = normalize(search);
+            if !key.is_empty() && seen.insert(key) {
+                related.push(search.trim().to_owned());
+            }
+        }
+    }
+    related.truncate(MAX_RELATED);
+    response.related = related;
+
+    response.total = answers.iter().filter_map(|answer| answer.total).max();
+}
+
+/// A query as far as telling queries apart goes, ignoring case and spacing
+fn normalize(query: &str) -> String {
+    query.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(correction: Option<&str>, related: &[&str], total: Option<u64>) -> ProviderResponse {
+        ProviderResponse {
+            correction: correction.map(str::to_owned),
+            related: related.iter().map(|search| search.to_string()).collect(),
+            total,
+            ..Default::default()
+        }
+    }
+
+    fn combined(query: &str, answers: &[ProviderResponse]) -> SearchResponse {
+        let mut response = SearchResponse::default();
+        combine(query, answers, &mut response);
+        response
+    }
+
+    #[test]
+    fn most_voted_correction_comes_first() {
+        let response = combined(
+            "rust programing",
+            &[
+                answer(Some("rust programming"), &[], None),
+                answer(Some("rest programing"), &[], None),
+                answer(Some("rust programming"), &[], None),
+            ],
+        );
+        assert_eq!(response.corrections, ["rust programming", "rest programing"]);
+    }
+
+    #[test]
+    fn tied_corrections_keep_provider_order() {
+        let response = combined(
+            "rust programing",
+            &[answer(Some("rest programing"), &[], None), answer(Some("rust programming"), &[], None)],
+        );
+        assert_eq!(response.corrections, ["rest programing", "rust programming"]);
+    }
+
+    #[test]
+    fn corrections_ignore_case_and_spacing() {
+        let response = combined(
+            "rust programing",
+            &[
+                answer(Some(" Rust  Programming"), &[], None),
+                answer(Some("rust programming"), &[], None),
+                answer(Some("RUST programing"), &[], None),
+                answer(Some(""), &[], None),
+            ],
+        );
+        assert_eq!(response.corrections, ["Rust  Programming"]);
+    }
+
+    #[test]
+    fn related_searches_take_turns() {
+        let response = combined(
+            "rust",
+            &[
+                answer(None, &["rust book", "Rust", "cargo"], None),
+                answer(None, &["rust game", "Rust  Book"], None),
+            ],
+        );
+        assert_eq!(response.related, ["rust book", "rust game", "cargo"]);
+
+        let many = (0..20).map(|i| format!("rust {i}")).collect::<Vec<_>>();
+        let many = many.iter().map(String::as_str).collect::<Vec<_>>();
+        let response = combined("rust", &[answer(None, &many, None)]);
+        assert_eq!(response.related.len(), MAX_RELATED);
+    }
+
+    #[test]
+    fn total_is_the_largest_estimate() {
+        let response = combined(
+            "rust",
+            &[answer(None, &[], Some(10)), answer(None, &[], None), answer(None, &[], Some(30))],
+        );
+        assert_eq!(response.total, Some(30));
+        assert_eq!(combined("rust", &[answer(None, &[], None)]).total, None);
+    }
+}
@@ -1,4 +1,18 @@
 mod api;
+mod cache;
 mod engine;
+mod health;
+mod limits;
+mod merger;
+mod meta;
+mod operators;
+mod pool;
+mod ranker;
+mod recency;
+#[cfg(feature = "hot_reload")]
+mod reload;
+mod transport;
 
 pub use engine::PluginEngine;
+pub use health::ProviderHealth;
+pub use transport::{FixtureRequest, FixtureStore, Transport};
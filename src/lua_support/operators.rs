@@ -0,0 +1,186 @@
+use searched_parser::{Term, TermKind};
+use url::Url;
+
+use crate::SearchResult;
+
+/// Drop results that go against the query's operators
+///
+/// Not every provider understands operators, and the ones that do don't
+/// always follow them. `site:`, `filetype:` and `intitle:` are checked on
+/// every result. Words and phrases are only checked when excluded, since
+/// providers match them loosely and snippets are cut short.
+pub(super) fn enforce(query: &str, results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let parsed = searched_parser::Query::parse(query);
+    if !parsed.terms.iter().any(is_checked) {
+        return results;
+    }
+
+    results
+        .into_iter()
+        .filter(|res| parsed.terms.iter().all(|term| satisfies(term, res)))
+        .collect()
+}
+
+/// Whether checking a term can drop any results
+fn is_checked(term: &Term) -> bool {
+    match &term.kind {
+        TermKind::Word(_) | TermKind::Phrase(_) => term.negated,
+        TermKind::Or(group) => group.iter().all(is_checked),
+        _ => true,
+    }
+}
+
+fn satisfies(term: &Term, res: &SearchResult) -> bool {
+    let matches = match &term.kind {
+        TermKind::Word(text) | TermKind::Phrase(text) => {
+            if !term.negated {
+                return true;
+            }
+            let snippet = res.general.as_ref().and_then(|g| g.snippet.as_deref());
+            contains_words(&res.title, text) || snippet.is_some_and(|s| contains_words(s, text))
+        }
+        TermKind::Site(site) => on_site(&res.url, site),
+        TermKind::FileType(ext) => res.url.path().to_lowercase().ends_with(&format!(".{ext}")),
+        TermKind::InTitle(text) => contains_words(&res.title, text),
+        TermKind::Or(group) => group.iter().any(|term| satisfies(term, res)),
+    };
+
+    matches != term.negated
+}
+
+/// Whether a URL is on a host, or under a path of it like `example.com/blog`
+fn on_site(url: &Url, site: &str) -> bool {
+    let (host, path) = match site.split_once('/') {
+        Some((host, path)) => (host, path.trim_end_matches('/')),
+        None => (site, ""),
+    };
+
+    let url_host = url.host_str().unwrap_or_default().to_lowercase();
+    let on_host = url_host == host
+        || url_host
+            .strip_suffix(host)
+            .is_some_and(|sub| sub.ends_with('.'));
+    on_host
+        && url
+            .path()
+            .trim_start_matches('/')
+            .strip_prefix(path)
+            .is_some_and(|rest| path.is_empty() || rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Whether the words of `needle` appear in `text` in order, ignoring case
+/// and punctuation
+fn contains_words(text: &str, needle: &str) -> bool {
+    let words = |s: &str| {
+        s.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+
+    let needle = words(needle);
+    !needle.is_empty()
+        && words(text)
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeneralResult;
+
+    fn result(url: &str, title: &str, snippet: &str) -> SearchResult {
+        SearchResult {
+            url: Url::parse(url).unwrap(),
+            title: title.to_owned(),
+            providers: Vec::new(),
+            general: Some(GeneralResult {
+                snippet: Some(snippet.to_owned()),
+            }),
+            forum: None,
+            image: None,
+            date: None,
+            positions: Default::default(),
+            annotations: Vec::new(),
+        }
+    }
+
+    fn sample() -> Vec<SearchResult> {
+        vec![
+            result("https://example.com/guide.pdf", "Rust guide", "Learn Rust"),
+            result("https://blog.example.com/posts/1", "Java vs Rust", "Which is faster"),
+            result("https://example.com/blog/rust", "Rust blog", "Spam and eggs"),
+            result("https://example.org/", "Other site", "Rust and Java"),
+        ]
+    }
+
+    /// URLs of the results kept for a query
+    fn kept(query: &str) -> Vec<String> {
+        enforce(query, sample())
+            .into_iter()
+            .map(|res| res.url.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn plain_words_keep_everything() {
+        assert_eq!(kept(r#"rust "no such phrase" java OR go"#).len(), 4);
+    }
+
+    #[test]
+    fn site() {
+        assert_eq!(
+            kept("site:example.com"),
+            [
+                "https://example.com/guide.pdf",
+                "https://blog.example.com/posts/1",
+                "https://example.com/blog/rust",
+            ]
+        );
+        assert_eq!(kept("site:example.com/blog"), ["https://example.com/blog/rust"]);
+        assert_eq!(kept("site:ample.com"), Vec::<String>::new());
+        assert_eq!(kept("-site:example.com"), ["https://example.org/"]);
+    }
+
+    #[test]
+    fn filetype() {
+        assert_eq!(kept("filetype:pdf"), ["https://example.com/guide.pdf"]);
+        assert_eq!(kept("-ext:pdf").len(), 3);
+    }
+
+    #[test]
+    fn intitle() {
+        assert_eq!(kept("intitle:java"), ["https://blog.example.com/posts/1"]);
+        assert_eq!(kept(r#"intitle:"rust blog""#), ["https://example.com/blog/rust"]);
+        assert_eq!(kept("-intitle:rust"), ["https://example.org/"]);
+    }
+
+    #[test]
+    fn excluded_words_and_phrases() {
+        // Titles and snippets both count
+        assert_eq!(
+            kept("-java"),
+            ["https://example.com/guide.pdf", "https://example.com/blog/rust"]
+        );
+        assert_eq!(kept(r#"-"spam and eggs""#).len(), 3);
+        // Whole words only
+        assert_eq!(kept("-jav").len(), 4);
+    }
+
+    #[test]
+    fn or_groups() {
+        assert_eq!(
+            kept("filetype:pdf OR site:example.org"),
+            ["https://example.com/guide.pdf", "https://example.org/"]
+        );
+        // Any unchecked term in a group could be what matched
+        assert_eq!(kept("filetype:pdf OR rust").len(), 4);
+        // Dropped only if it has both
+        assert_eq!(
+            kept("-java OR -rust"),
+            ["https://example.com/guide.pdf", "https://example.com/blog/rust"]
+        );
+    }
+}
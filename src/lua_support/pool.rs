@@ -0,0 +1,96 @@
+use std::{
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use mlua::Lua;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// A fixed set of independent Lua states
+///
+/// Each call gets a state to itself until it's done, waiting for one to free
+/// up if they're all taken. Lua code on different states runs in parallel.
+///
+/// Calls can't share a state: Lua only has room for one instruction hook,
+/// and the time limit of every call relies on its own.
+pub(super) struct LuaPool {
+    states: Vec<Lua>,
+    free: Mutex<Vec<usize>>,
+    available: Arc<Semaphore>,
+}
+impl LuaPool {
+    pub fn new(states: Vec<Lua>) -> Self {
+        assert!(!states.is_empty(), "Lua pool needs at least one state");
+
+        Self {
+            free: Mutex::new((0..states.len()).collect()),
+            available: Arc::new(Semaphore::new(states.len())),
+            states,
+        }
+    }
+
+    /// Take a free Lua state, waiting for one if needed
+    pub async fn get(self: &Arc<Self>) -> PooledLua {
+        let permit = self
+            .available
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the pool never closes its semaphore");
+        let index = self
+            .free
+            .lock()
+            .unwrap()
+            .pop()
+            .expect("a permit always leaves a state free");
+
+        PooledLua {
+            lua: self.states[index].clone(),
+            index,
+            pool: self.clone(),
+            _permit: permit,
+        }
+    }
+
+    /// Take every state at once, waiting for the calls running on them
+    #[cfg(feature = "hot_reload")]
+    pub async fn get_all(self: &Arc<Self>) -> Vec<PooledLua> {
+        let mut permits = self
+            .available
+            .clone()
+            .acquire_many_owned(self.states.len() as u32)
+            .await
+            .expect("the pool never closes its semaphore");
+        let free = std::mem::take(&mut *self.free.lock().unwrap());
+
+        free.into_iter()
+            .map(|index| PooledLua {
+                lua: self.states[index].clone(),
+                index,
+                pool: self.clone(),
+                _permit: permits.split(1).expect("there's a permit for every state"),
+            })
+            .collect()
+    }
+}
+
+/// A Lua state handed out by a [LuaPool]
+pub(super) struct PooledLua {
+    lua: Lua,
+    index: usize,
+    pool: Arc<LuaPool>,
+    /// Released after the state is back in the free list
+    _permit: OwnedSemaphorePermit,
+}
+impl Deref for PooledLua {
+    type Target = Lua;
+
+    fn deref(&self) -> &Lua {
+        &self.lua
+    }
+}
+impl Drop for PooledLua {
+    fn drop(&mut self) {
+        self.pool.free.lock().unwrap().push(self.index);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use crate::SearchResult;
+
+/// Name the native ranker is used by
+///
+/// A Lua ranker registered under the same name replaces it.
+pub(super) const NAME: &str = "rrf";
+
+/// Default for `k`, the value from the original RRF paper
+const DEFAULT_K: f64 = 60.0;
+
+/// Weigh results with Reciprocal Rank Fusion
+///
+/// Every provider adds `weight / (k + position)` to each result it returned,
+/// so results placed high by many providers come out on top. Options:
+///
+/// - `k`: how much lower positions are flattened, 60 by default
+/// - `weights`: a table of provider names to weights, 1 by default
+pub(super) fn rank(results: &[SearchResult], options: &HashMap<String, toml::Value>) -> Vec<f64> {
+    let k = options.get("k").and_then(as_number).unwrap_or(DEFAULT_K);
+    let weights = options.get("weights").and_then(|w| w.as_table());
+
+    results
+        .iter()
+        .map(|res| {
+            res.positions
+                .iter()
+                .map(|(provider, pos)| {
+                    let weight = weights
+                        .and_then(|w| w.get(provider))
+                        .and_then(as_number)
+                        .unwrap_or(1.0);
+                    weight / (k + *pos as f64)
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Multiply the `weights` in ranker options by a user's provider weights
+///
+/// Any ranker can read these, not only the native one.
+pub(super) fn apply_weights<'a>(
+    options: &mut HashMap<String, toml::Value>,
+    user_weights: impl Iterator<Item = (&'a String, f64)>,
+) {
+    let mut user_weights = user_weights.peekable();
+    if user_weights.peek().is_none() {
+        return;
+    }
+
+    let weights = options
+        .entry("weights".to_owned())
+        .or_insert_with(|| toml::Value::Table(Default::default()));
+    let Some(weights) = weights.as_table_mut() else {
+        warn!("ranker option weights isn't a table, ignoring provider weights");
+        return;
+    };
+
+    for (provider, weight) in user_weights {
+        let current = weights.get(provider).and_then(as_number).unwrap_or(1.0);
+        weights.insert(provider.clone(), toml::Value::Float(current * weight));
+    }
+}
+
+/// Read a TOML integer or float
+fn as_number(value: &toml::Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|n| n as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use url::Url;
+
+    use super::*;
+
+    /// Warnings logged while the tests run
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CaptureWarnings;
+    impl log::Log for CaptureWarnings {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn result(positions: &[(&str, usize)]) -> SearchResult {
+        SearchResult {
+            url: Url::parse("https://example.com/").unwrap(),
+            title: String::new(),
+            providers: positions.iter().map(|(provider, _)| provider.to_string()).collect(),
+            general: None,
+            forum: None,
+            image: None,
+            date: None,
+            positions: positions.iter().map(|(provider, pos)| (provider.to_string(), *pos)).collect(),
+            annotations: Vec::new(),
+        }
+    }
+
+    fn options(toml: &str) -> HashMap<String, toml::Value> {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn agreement_beats_a_single_first_place() {
+        let results = [result(&[("a", 2), ("b", 3)]), result(&[("c", 1)])];
+        let weights = rank(&results, &HashMap::new());
+        assert!(weights[0] > weights[1], "{weights:?}");
+    }
+
+    #[test]
+    fn honours_k() {
+        let results = [result(&[("a", 1)])];
+        assert_eq!(rank(&results, &options("k = 1")), [0.5]);
+        assert_eq!(rank(&results, &options("k = 9.0")), [0.1]);
+    }
+
+    #[test]
+    fn honours_weights() {
+        let results = [result(&[("a", 1)]), result(&[("b", 1)])];
+        let weights = rank(&results, &options("k = 0\nweights = { a = 3, b = 0.5 }"));
+        assert_eq!(weights, [3.0, 0.5]);
+    }
+
+    #[test]
+    fn user_weights_multiply_configured_ones() {
+        let mut opts = options("weights = { a = 2 }");
+        let (a, b) = ("a".to_owned(), "b".to_owned());
+        apply_weights(&mut opts, [(&a, 1.5), (&b, 2.0)].into_iter());
+        assert_eq!(opts, options("weights = { a = 3.0, b = 2.0 }"));
+    }
+
+    #[test]
+    fn no_user_weights_leave_options_alone() {
+        let mut opts = HashMap::new();
+        apply_weights(&mut opts, std::iter::empty());
+        assert!(opts.is_empty());
+    }
+
+    #[test]
+    fn warns_when_weights_isnt_a_table() {
+        let _ = log::set_logger(&CaptureWarnings);
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let mut opts = options("weights = 'heavy'");
+        let a = "a".to_owned();
+        apply_weights(&mut opts, [(&a, 2.0)].into_iter());
+
+        assert_eq!(opts, options("weights = 'heavy'"));
+        assert!(
+            WARNINGS.lock().unwrap().iter().any(|w| w.contains("weights isn't a table")),
+            "no warning logged"
+        );
+    }
+}
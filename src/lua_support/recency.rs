@@ -0,0 +1,132 @@
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+
+use crate::{SearchResult, TimeRange};
+
+/// Drop results dated before the start of the time range
+///
+/// Providers that can't filter by date send everything, so this catches
+/// what they let through. Results without a date are kept.
+pub(super) fn enforce(range: Option<TimeRange>, results: Vec<SearchResult>) -> Vec<SearchResult> {
+    let Some(range) = range else {
+        return results;
+    };
+    let since = Utc::now() - range.duration();
+
+    results
+        .into_iter()
+        .filter(|res| {
+            res.date
+                .as_deref()
+                .and_then(parse_date)
+                .is_none_or(|date| date >= since)
+        })
+        .collect()
+}
+
+/// Write a result's date as RFC 3339, or drop it if it can't be read
+pub(super) fn normalize(date: Option<String>) -> Option<String> {
+    date.as_deref()
+        .and_then(parse_date)
+        .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Unix seconds an engine may give, September 2001 to November 2286
+///
+/// Other numbers are more likely years or milliseconds than seconds.
+const TIMESTAMPS: std::ops::Range<i64> = 1_000_000_000..10_000_000_000;
+
+/// Read an RFC 3339 date, Unix seconds, or `YYYY-MM-DD` as the end of that day
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.to_utc())
+        .ok()
+        .or_else(|| {
+            let secs = date.parse().ok().filter(|secs| TIMESTAMPS.contains(secs))?;
+            DateTime::from_timestamp(secs, 0)
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|day| day.and_hms_opt(23, 59, 59))
+                .map(|date| date.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use url::Url;
+
+    use super::*;
+
+    fn dated(date: Option<&str>) -> SearchResult {
+        SearchResult {
+            url: Url::parse("https://example.com/").unwrap(),
+            title: date.unwrap_or("undated").to_owned(),
+            providers: Vec::new(),
+            general: None,
+            forum: None,
+            image: None,
+            date: date.map(str::to_owned),
+            positions: Default::default(),
+            annotations: Vec::new(),
+        }
+    }
+
+    fn normalized(date: &str) -> Option<String> {
+        normalize(Some(date.to_owned()))
+    }
+
+    #[test]
+    fn reads_rfc_3339() {
+        assert_eq!(normalized("2024-03-01T12:00:00+02:00").as_deref(), Some("2024-03-01T10:00:00Z"));
+    }
+
+    #[test]
+    fn reads_days_as_their_end() {
+        assert_eq!(normalized(" 2024-03-01 ").as_deref(), Some("2024-03-01T23:59:59Z"));
+    }
+
+    #[test]
+    fn reads_unix_seconds() {
+        assert_eq!(normalized("1700000000").as_deref(), Some("2023-11-14T22:13:20Z"));
+    }
+
+    #[test]
+    fn drops_numbers_that_arent_seconds() {
+        // A bare year and milliseconds
+        assert_eq!(normalized("2024"), None);
+        assert_eq!(normalized("1700000000000"), None);
+    }
+
+    #[test]
+    fn drops_garbage() {
+        assert_eq!(normalized("yesterday"), None);
+        assert_eq!(normalized("2024-13-45"), None);
+        assert_eq!(normalized(""), None);
+    }
+
+    #[test]
+    fn enforce_drops_only_older_results() {
+        let recent = (Utc::now() - Duration::hours(1)).to_rfc3339();
+        let results = vec![
+            dated(Some(&recent)),
+            dated(Some("2001-09-09")),
+            dated(None),
+            dated(Some("2024")),
+        ];
+
+        let kept = enforce(Some(TimeRange::Day), results)
+            .into_iter()
+            .map(|res| res.title)
+            .collect::<Vec<_>>();
+        assert_eq!(kept, [recent.as_str(), "undated", "2024"]);
+    }
+
+    #[test]
+    fn enforce_keeps_everything_without_a_range() {
+        let results = vec![dated(Some("2001-09-09")), dated(None)];
+        assert_eq!(enforce(None, results).len(), 2);
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{read_dir, read_to_string},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use mlua::prelude::*;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::{sync::mpsc, time::timeout};
+
+use super::{
+    engine::{PLUGIN_DIRS, PluginEngine, REGISTRIES},
+    pool::{LuaPool, PooledLua},
+};
+use crate::{
+    Error,
+    config::{CfgPlugins, ProvidersConfig},
+};
+
+/// How long to wait for more changes before reloading
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Source of every loaded plugin, by path relative to `plugins/`
+type Sources = BTreeMap<PathBuf, String>;
+
+/// Callbacks in the registries, by registry and name
+type Registered = Vec<(&'static str, String, LuaFunction)>;
+
+/// What each plugin registered on a Lua state, kept in its app data
+#[derive(Default)]
+struct Owners(HashMap<PathBuf, Vec<(&'static str, String)>>);
+
+/// Watch `plugins/` and reload whatever changes in the background
+///
+/// Only changed plugins are run again, on every Lua state of the pool, and
+/// deleted plugins have what they registered taken out. Plugins that fail to
+/// load keep their last good version. `providers.toml` is swapped out as a
+/// whole.
+pub(super) fn watch(
+    pool: Arc<RwLock<Arc<LuaPool>>>,
+    providers: Arc<RwLock<Arc<ProvidersConfig>>>,
+    cfg: CfgPlugins,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let root = Path::new("plugins").canonicalize()?;
+    let mut sources = read_sources(&root);
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        match res {
+            // We open files ourselves when reloading, don't react to that
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => {
+                let _ = tx.send(event.paths);
+            }
+            Err(err) => warn!("plugin watcher error: {err}"),
+        }
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    debug!("watching {} for changes", root.display());
+
+    tokio::spawn(async move {
+        // The watcher stops when dropped
+        let _watcher = watcher;
+
+        while let Some(first) = rx.recv().await {
+            // Editors tend to touch a file several times per save, wait
+            // for things to settle before reloading anything
+            let mut paths = first.into_iter().collect::<BTreeSet<PathBuf>>();
+            while let Ok(Some(more)) = timeout(DEBOUNCE, rx.recv()).await {
+                paths.extend(more);
+            }
+
+            let mut changed = Vec::new();
+            for path in paths {
+                let Ok(rel) = path.strip_prefix(&root) else {
+                    continue;
+                };
+
+                if rel == Path::new("providers.toml") {
+                    reload_providers(&path, &providers);
+                } else if is_plugin(rel) {
+                    changed.push(rel.to_owned());
+                }
+            }
+
+            if !changed.is_empty() {
+                reload_plugins(&root, &changed, &mut sources, &pool, &cfg).await;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Whether a path relative to `plugins/` is a Lua plugin we load
+fn is_plugin(rel: &Path) -> bool {
+    rel.extension().is_some_and(|ext| ext == "lua")
+        && rel
+            .parent()
+            .and_then(Path::to_str)
+            .is_some_and(|dir| PLUGIN_DIRS.contains(&dir))
+}
+
+/// Read every plugin the way the engine loaded them at startup
+fn read_sources(root: &Path) -> Sources {
+    let mut sources = Sources::new();
+    for dir in PLUGIN_DIRS {
+        let Ok(entries) = read_dir(root.join(dir)) else {
+            continue;
+        };
+
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let Ok(rel) = path.strip_prefix(root) else {
+                continue;
+            };
+            match read_to_string(&path) {
+                Ok(src) => {
+                    sources.insert(rel.to_owned(), src);
+                }
+                Err(err) => warn!("couldn't read {}: {err}", path.display()),
+            }
+        }
+    }
+    sources
+}
+
+fn reload_providers(path: &Path, providers: &RwLock<Arc<ProvidersConfig>>) {
+    match ProvidersConfig::try_load(path) {
+        Ok(cfg) => {
+            *providers.write().unwrap() = Arc::new(cfg);
+            info!("reloaded providers.toml");
+        }
+        Err(err) => error!("keeping the previous providers.toml, reload failed: {err}"),
+    }
+}
+
+/// Run the changed plugins again on every Lua state
+///
+/// Every state is taken first, so no call sees some of the changes and not
+/// others. If a state can't be put back in order after a plugin fails, a
+/// whole new pool is built instead.
+async fn reload_plugins(
+    root: &Path,
+    changed: &[PathBuf],
+    sources: &mut Sources,
+    pool: &RwLock<Arc<LuaPool>>,
+    cfg: &CfgPlugins,
+) {
+    let mut next = sources.clone();
+    let mut pending = Vec::new();
+    for rel in changed {
+        match read_to_string(root.join(rel)) {
+            Ok(src) if sources.get(rel) == Some(&src) => {}
+            Ok(src) => {
+                next.insert(rel.clone(), src);
+                pending.push(rel.clone());
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                if next.remove(rel).is_some() {
+                    pending.push(rel.clone());
+                }
+            }
+            Err(err) => error!("keeping the previous version of {}, couldn't read it: {err}", rel.display()),
+        }
+    }
+    if pending.is_empty() {
+        return;
+    }
+
+    let call_limit = Duration::from_millis(cfg.call_time_limit);
+    let current = pool.read().unwrap().clone();
+    let states = current.get_all().await;
+
+    let mut reloaded = Vec::with_capacity(pending.len());
+    for rel in pending {
+        match reload_plugin(&states, &rel, next.get(&rel), call_limit).await {
+            Ok(()) => reloaded.push(rel),
+            Err(Failure::Plugin(err)) => {
+                error!("keeping the previous version of {}, reload failed: {err}", rel.display());
+                match sources.get(&rel) {
+                    Some(src) => next.insert(rel.clone(), src.clone()),
+                    None => next.remove(&rel),
+                };
+            }
+            Err(Failure::Broken(err)) => {
+                error!("couldn't reload {} in place, rebuilding every Lua state: {err}", rel.display());
+                // Nothing may run on the broken states until the new pool is in
+                rebuild(next, sources, pool, cfg).await;
+                return;
+            }
+        }
+    }
+
+    *sources = next;
+    for rel in reloaded {
+        if sources.contains_key(&rel) {
+            info!("reloaded {}", rel.display());
+        } else {
+            info!("unloaded {}, it was removed", rel.display());
+        }
+    }
+}
+
+/// What went wrong reloading a plugin in place
+enum Failure {
+    /// The plugin failed, every state has its previous version back
+    Plugin(Error),
+    /// A state couldn't be put back the way it was
+    Broken(Error),
+}
+
+/// Run one plugin again on every state, or only take it out if it's gone
+async fn reload_plugin(
+    states: &[PooledLua],
+    rel: &Path,
+    src: Option<&String>,
+    call_limit: Duration,
+) -> Result<(), Failure> {
+    let mut previous = Vec::with_capacity(states.len());
+    for lua in states {
+        previous.push((lua, unregister(lua, rel).map_err(|err| Failure::Broken(err.into()))?));
+
+        let Some(src) = src else {
+            continue;
+        };
+        if let Err(err) = PluginEngine::run_plugin(lua, rel, src, call_limit).await {
+            for (lua, registered) in previous {
+                restore(lua, rel, registered).map_err(|err| Failure::Broken(err.into()))?;
+            }
+            return Err(Failure::Plugin(err));
+        }
+    }
+    Ok(())
+}
+
+/// Every callback in the registries
+pub(super) fn snapshot(lua: &Lua) -> LuaResult<Registered> {
+    let mut registered = Vec::new();
+    for registry in REGISTRIES {
+        for pair in lua.globals().get::<LuaTable>(*registry)?.pairs::<String, LuaFunction>() {
+            let (name, callback) = pair?;
+            registered.push((*registry, name, callback));
+        }
+    }
+    Ok(registered)
+}
+
+/// Note down what a plugin registered since `before` as its own
+pub(super) fn claim(lua: &Lua, rel: &Path, before: &Registered) -> LuaResult<()> {
+    let claimed = snapshot(lua)?
+        .into_iter()
+        .filter(|(registry, name, callback)| {
+            !before.iter().any(|(r, n, c)| r == registry && n == name && c.to_pointer() == callback.to_pointer())
+        })
+        .map(|(registry, name, _)| (registry, name))
+        .collect();
+    own(lua, rel, claimed);
+    Ok(())
+}
+
+fn own(lua: &Lua, rel: &Path, claimed: Vec<(&'static str, String)>) {
+    if lua.app_data_ref::<Owners>().is_none() {
+        lua.set_app_data(Owners::default());
+    }
+    let mut owners = lua.app_data_mut::<Owners>().unwrap();
+
+    // Plugins loaded later replace what earlier ones registered
+    for owned in owners.0.values_mut() {
+        owned.retain(|entry| !claimed.contains(entry));
+    }
+    owners.0.insert(rel.to_owned(), claimed);
+}
+
+/// Take out everything a plugin registered, returning it
+fn unregister(lua: &Lua, rel: &Path) -> LuaResult<Registered> {
+    let owned = lua
+        .app_data_mut::<Owners>()
+        .and_then(|mut owners| owners.0.remove(rel))
+        .unwrap_or_default();
+
+    let mut registered = Vec::with_capacity(owned.len());
+    for (registry, name) in owned {
+        let table = lua.globals().get::<LuaTable>(registry)?;
+        if let Some(callback) = table.get::<Option<LuaFunction>>(name.as_str())? {
+            table.set(name.as_str(), LuaNil)?;
+            registered.push((registry, name, callback));
+        }
+    }
+    Ok(registered)
+}
+
+/// Put back what a plugin registered before a failed reload
+fn restore(lua: &Lua, rel: &Path, registered: Registered) -> LuaResult<()> {
+    unregister(lua, rel)?;
+
+    let mut claimed = Vec::with_capacity(registered.len());
+    for (registry, name, callback) in registered {
+        lua.globals().get::<LuaTable>(registry)?.set(name.as_str(), callback)?;
+        claimed.push((registry, name));
+    }
+    own(lua, rel, claimed);
+    Ok(())
+}
+
+/// Build a whole new pool running these plugins and swap it in
+///
+/// Plugins that fail to load keep the version in `sources`.
+async fn rebuild(mut next: Sources, sources: &mut Sources, pool: &RwLock<Arc<LuaPool>>, cfg: &CfgPlugins) {
+    let states = loop {
+        match build_states(&next, cfg).await {
+            Ok(states) => break states,
+            Err((Some(rel), err)) if next.get(&rel) != sources.get(&rel) => {
+                error!("keeping the previous version of {}, reload failed: {err}", rel.display());
+                match sources.get(&rel) {
+                    Some(src) => next.insert(rel.clone(), src.clone()),
+                    None => next.remove(&rel),
+                };
+            }
+            Err((rel, err)) => {
+                let culprit = rel.map_or_else(String::new, |rel| format!(" on {}", rel.display()));
+                error!("couldn't rebuild the Lua states, failed{culprit}: {err}");
+                return;
+            }
+        }
+    };
+
+    *pool.write().unwrap() = Arc::new(LuaPool::new(states));
+    *sources = next;
+    info!("rebuilt every Lua state");
+}
+
+/// Create a full pool's worth of Lua states running these plugins
+///
+/// Fails with the plugin at fault, if any.
+async fn build_states(sources: &Sources, cfg: &CfgPlugins) -> Result<Vec<Lua>, (Option<PathBuf>, Error)> {
+    let call_limit = Duration::from_millis(cfg.call_time_limit);
+
+    let mut states = Vec::with_capacity(cfg.pool_size());
+    for _ in 0..cfg.pool_size() {
+        let lua = PluginEngine::new_lua(cfg).map_err(|err| (None, err.into()))?;
+
+        // Same order as at startup
+        for dir in PLUGIN_DIRS {
+            for (rel, src) in sources.iter().filter(|(rel, _)| rel.parent() == Some(Path::new(dir))) {
+                PluginEngine::run_plugin(&lua, rel, src, call_limit)
+                    .await
+                    .map_err(|err| (Some(rel.clone()), err))?;
+            }
+        }
+
+        states.push(lua);
+    }
+    Ok(states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALL_LIMIT: Duration = Duration::from_millis(1000);
+
+    async fn pool(src: &str) -> Arc<LuaPool> {
+        let lua = PluginEngine::new_lua(&CfgPlugins::default()).unwrap();
+        PluginEngine::run_plugin(&lua, Path::new("engines/test.lua"), src, CALL_LIMIT)
+            .await
+            .unwrap();
+        Arc::new(LuaPool::new(vec![lua]))
+    }
+
+    fn engines(lua: &Lua) -> Vec<String> {
+        let mut names = snapshot(lua)
+            .unwrap()
+            .into_iter()
+            .map(|(_, name, _)| name)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    async fn reload(pool: &Arc<LuaPool>, src: Option<&str>) -> Result<(), Failure> {
+        let states = pool.get_all().await;
+        let src = src.map(str::to_owned);
+        reload_plugin(&states, Path::new("engines/test.lua"), src.as_ref(), CALL_LIMIT).await
+    }
+
+    #[tokio::test]
+    async fn replaces_what_the_plugin_registered() {
+        let pool = pool("add_engine('a', function() end) add_engine('b', function() end)").await;
+        assert!(reload(&pool, Some("add_engine('b', function() end)")).await.is_ok());
+        assert_eq!(engines(&*pool.get().await), ["b"]);
+    }
+
+    #[tokio::test]
+    async fn removed_plugins_are_taken_out() {
+        let pool = pool("add_engine('a', function() end)").await;
+        assert!(reload(&pool, None).await.is_ok());
+        assert!(engines(&*pool.get().await).is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_reloads_keep_the_previous_version() {
+        let pool = pool("add_engine('a', function() end)").await;
+        let res = reload(&pool, Some("add_engine('b', function() end) error('oops')")).await;
+        assert!(matches!(res, Err(Failure::Plugin(_))));
+        assert_eq!(engines(&*pool.get().await), ["a"]);
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use reqwest::Client;
+
+use crate::Error;
+
+/// Where requests made by engines go
+#[derive(Clone)]
+pub enum Transport {
+    /// Straight to the network
+    Live(Client),
+    /// To the network, saving every response to a fixture store
+    Record(Client, FixtureStore),
+    /// Only to a fixture store, nothing touches the network
+    Replay(FixtureStore),
+}
+
+impl Transport {
+    /// The transport a provider's engine uses, fixtures are kept apart by provider
+    pub(super) fn for_provider(&self, provider: &str) -> Self {
+        match self {
+            Transport::Live(client) => Transport::Live(client.clone()),
+            Transport::Record(client, store) => {
+                Transport::Record(client.clone(), store.subdir(provider))
+            }
+            Transport::Replay(store) => Transport::Replay(store.subdir(provider)),
+        }
+    }
+}
+
+/// What makes two requests the same as far as fixtures go
+///
+/// Headers are left out, they don't change what an upstream answers often
+/// enough to be worth the churn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub form: Option<BTreeMap<String, String>>,
+    #[serde(default)]
+    pub json: Option<serde_json::Value>,
+}
+
+impl FixtureRequest {
+    /// A stable name for the request, the same across runs and platforms
+    fn key(&self) -> String {
+        // FNV-1a, std's hashers aren't guaranteed to stay the same
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes.iter().chain(b"\0") {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        feed(self.method.as_bytes());
+        feed(self.url.as_bytes());
+        for (key, value) in self.form.iter().flatten() {
+            feed(key.as_bytes());
+            feed(value.as_bytes());
+        }
+        if let Some(json) = &self.json {
+            feed(json.to_string().as_bytes());
+        }
+
+        format!("{hash:016x}")
+    }
+}
+
+/// A recorded response and the request it answers
+#[derive(Debug, Serialize, Deserialize)]
+struct Fixture {
+    request: FixtureRequest,
+    body: String,
+}
+
+/// A directory of recorded responses, one JSON file per request
+#[derive(Debug, Clone)]
+pub struct FixtureStore {
+    dir: PathBuf,
+}
+
+impl FixtureStore {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    fn subdir(&self, name: &str) -> Self {
+        Self {
+            dir: self.dir.join(name),
+        }
+    }
+
+    fn path(&self, request: &FixtureRequest) -> PathBuf {
+        self.dir.join(format!("{}.json", request.key()))
+    }
+
+    /// Get the recorded response to a request
+    pub fn load(&self, request: &FixtureRequest) -> Result<String, Error> {
+        let path = self.path(request);
+        let raw = fs::read_to_string(&path).map_err(|err| {
+            Error::Fixture(format!(
+                "no fixture for {} {} at {}: {err}",
+                request.method,
+                request.url,
+                path.display()
+            ))
+        })?;
+        let fixture: Fixture = serde_json::from_str(&raw)
+            .map_err(|err| Error::Fixture(format!("{} is broken: {err}", path.display())))?;
+
+        // Two requests sharing a key is unlikely, but would be confusing
+        if fixture.request != *request {
+            return Err(Error::Fixture(format!(
+                "{} was recorded for another request",
+                path.display()
+            )));
+        }
+
+        Ok(fixture.body)
+    }
+
+    /// Save the response to a request, replacing any earlier recording
+    pub fn save(&self, request: &FixtureRequest, body: &str) -> Result<(), Error> {
+        let path = self.path(request);
+        let fixture = Fixture {
+            request: request.clone(),
+            body: body.to_owned(),
+        };
+
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| {
+                let raw = serde_json::to_string_pretty(&fixture).map_err(std::io::Error::other)?;
+                fs::write(&path, raw + "\n")
+            })
+            .map_err(|err| Error::Fixture(format!("couldn't save {}: {err}", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixture store in a fresh temporary directory
+    fn store(name: &str) -> FixtureStore {
+        let dir = std::env::temp_dir().join(format!("searched-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        FixtureStore::new(dir)
+    }
+
+    fn request(url: &str) -> FixtureRequest {
+        FixtureRequest {
+            method: "POST".to_owned(),
+            url: url.to_owned(),
+            form: Some([("q".to_owned(), "rust".to_owned())].into()),
+            json: None,
+        }
+    }
+
+    #[test]
+    fn replays_what_was_saved() {
+        let store = store("replays");
+        let req = request("https://example.com/search");
+        store.save(&req, "<html>results</html>").unwrap();
+
+        assert_eq!(store.load(&req).unwrap(), "<html>results</html>");
+        let _ = fs::remove_dir_all(&store.dir);
+    }
+
+    #[test]
+    fn missing_fixtures_are_errors() {
+        let store = store("missing");
+        let res = store.load(&request("https://example.com/search"));
+        assert!(matches!(res, Err(Error::Fixture(_))), "{res:?}");
+    }
+
+    #[test]
+    fn keys_depend_on_the_whole_request() {
+        let req = request("https://example.com/search");
+        let mut other_form = req.clone();
+        other_form.form = Some([("q".to_owned(), "go".to_owned())].into());
+
+        assert_eq!(req.key(), request("https://example.com/search").key());
+        assert_ne!(req.key(), request("https://example.com/other").key());
+        assert_ne!(req.key(), other_form.key());
+    }
+}
@@ -1,4 +1,5 @@
 extern crate axum;
+extern crate clap;
 extern crate env_logger;
 extern crate log;
 extern crate reqwest;
@@ -13,9 +14,14 @@ use mimalloc::MiMalloc;
 static GLOBAL: MiMalloc = MiMalloc;
 
 use searched::settings;
+mod cli;
 mod web;
+// The nested ifs in widgets and modules would need let chains to collapse,
+// which the Docker toolchain doesn't have yet
+#[allow(clippy::collapsible_if)]
 mod widgets;
 
+#[allow(clippy::collapsible_if)]
 mod modules {
     pub mod favicon;
     pub mod image_proxy;
@@ -23,15 +29,14 @@ mod modules {
     pub mod url_cleaner;
 }
 
-use axum::{
-    http::{HeaderMap, HeaderValue},
-    middleware,
-};
+use axum::middleware;
+use clap::Parser;
+use cli::{Cli, Command, ConfigCommand};
 use log::{LevelFilter, debug, error, info};
 use modules::url_cleaner;
 use reqwest::Client;
-use searched::lua_support::PluginEngine;
-use std::{error::Error, process};
+use searched::{config::Config, lua_support::PluginEngine};
+use std::{error::Error, path::Path, process, process::ExitCode};
 use tokio::net::TcpListener;
 
 #[derive(Clone)]
@@ -42,42 +47,61 @@ pub struct AppState {
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let start = std::time::Instant::now();
-
+async fn main() -> Result<ExitCode, Box<dyn Error>> {
+    let command = Cli::parse().command.unwrap_or(Command::Serve { listen: None });
+
+    // Only the server should be chatty, other commands print what they're asked for
+    let level = match command {
+        Command::Serve { .. } => LevelFilter::Info,
+        _ => LevelFilter::Warn,
+    };
     env_logger::builder()
-        .filter_level(LevelFilter::Info)
+        .filter_level(level)
         .parse_default_env()
         .init();
 
-    info!("Starting searched...");
+    match command {
+        // Checking has to survive a broken config
+        Command::Config(ConfigCommand::Check) => return Ok(cli::config_check().await),
+        Command::Serve { listen } => {
+            let (client, config) = setup()?;
+            serve(listen, client, config).await?
+        }
+        Command::Search(args) => {
+            let (client, config) = setup()?;
+            return cli::search(args, client, &config).await;
+        }
+        Command::EngineTest {
+            provider,
+            query,
+            json,
+        } => {
+            let (client, config) = setup()?;
+            return cli::engine_test(provider, query, json, client, &config).await;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Load the config and build the HTTP client, what every command but checking needs
+fn setup() -> Result<(Client, Config), Box<dyn Error>> {
+    debug!("Loading config");
+    let config = if Path::new("config.toml").exists() {
+        Config::load("config.toml")
+    } else {
+        Config::default()
+    };
+
     debug!("Configuring HTTP client");
+    Ok((searched::http_client()?, config))
+}
 
-    let mut headers = HeaderMap::new();
-    for (key, val) in [
-        (
-            "User-Agent",
-            "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:129.0) Gecko/20100101 Firefox/129.0",
-        ),
-        (
-            "Accept",
-            "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,*/*;q=0.8",
-        ),
-        ("Accept-Language", "en-US,en;q=0.5"),
-        ("Accept-Encoding", "gzip"),
-        ("DNT", "1"),
-        ("Connection", "keep-alive"),
-        ("Upgrade-Insecure-Requests", "1"),
-        ("Sec-Fetch-Dest", "document"),
-        ("Sec-Fetch-Mode", "navigate"),
-        ("Sec-Fetch-Site", "none"),
-        ("Sec-Fetch-User", "?1"),
-        ("Priority", "u=1"),
-        ("TE", "trailers"),
-    ] {
-        headers.append(key, HeaderValue::from_str(val)?);
-    }
-    let client = Client::builder().default_headers(headers).build()?;
+
+async fn serve(listen: Option<String>, client: Client, config: Config) -> Result<(), Box<dyn Error>> {
+    let start = std::time::Instant::now();
+
+    info!("Starting searched...");
 
     debug!("Opening database");
     let db = sled::open("data/db")?;
@@ -88,18 +112,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
     url_cleaner::ensure_rules_exist().await;
 
     debug!("Initializing plugin engine");
-    let eng = PluginEngine::new(client.clone()).await?;
+    let eng = PluginEngine::new(client.clone(), &config, &db).await?;
 
     info!("Setting up web server");
     let app = web::router()
         .with_state(AppState { eng, client, db })
         .layer(middleware::from_fn(settings::settings_middleware));
 
-    let bind_addr = "0.0.0.0:6969";
+    let bind_addr = listen
+        .or(config.listen_addr.clone())
+        .unwrap_or_else(|| "0.0.0.0:6969".to_owned());
     info!("Starting web server on {}", bind_addr);
 
     tokio::spawn(async move {
-        match TcpListener::bind(bind_addr).await {
+        match TcpListener::bind(&bind_addr).await {
             Ok(listener) => {
                 if let Err(e) = axum::serve(listener, app.into_make_service()).await {
                     error!("Server error: {}", e);
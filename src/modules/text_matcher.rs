@@ -14,7 +14,7 @@ pub fn highlight_text(text: &str, query: &str) -> String {
     let haystack = nucleo_matcher::Utf32Str::new(text, &mut buf);
 
     let mut indices = Vec::new();
-    if let Some(_) = pattern.indices(haystack, &mut matcher, &mut indices) {
+    if pattern.indices(haystack, &mut matcher, &mut indices).is_some() {
         indices.sort_unstable();
         indices.dedup();
 
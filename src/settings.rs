@@ -8,9 +8,103 @@ use axum::{
 use axum_extra::extract::CookieJar;
 use base64::{Engine as _, engine::general_purpose};
 use log::{debug, warn};
-use crate::SafeSearch;
+use crate::{ProviderOptions, SafeSearch, SearchOptions, bangs};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use url::Url;
+
+/// A service whose links can be rewritten to an alternative frontend
+#[derive(Debug, Serialize)]
+pub struct Frontend {
+    /// Service name, as the frontends postprocessor knows it
+    pub service: &'static str,
+    /// Human readable name of the service
+    pub name: &'static str,
+    /// Human readable name of the frontend
+    pub frontend: &'static str,
+    /// Instance used when the user doesn't pick one
+    pub default_instance: &'static str,
+}
+
+pub const FRONTENDS: &[Frontend] = &[
+    Frontend {
+        service: "youtube",
+        name: "YouTube",
+        frontend: "Invidious",
+        default_instance: "https://yewtu.be",
+    },
+    Frontend {
+        service: "reddit",
+        name: "Reddit",
+        frontend: "Redlib",
+        default_instance: "https://safereddit.com",
+    },
+];
+
+/// Languages results can be asked in, as ISO 639-1 codes and names
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("ar", "Arabic"),
+    ("zh", "Chinese"),
+    ("cs", "Czech"),
+    ("da", "Danish"),
+    ("nl", "Dutch"),
+    ("en", "English"),
+    ("fi", "Finnish"),
+    ("fr", "French"),
+    ("de", "German"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("no", "Norwegian"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("es", "Spanish"),
+    ("sv", "Swedish"),
+    ("tr", "Turkish"),
+    ("uk", "Ukrainian"),
+];
+
+/// Regions results can be asked for, as ISO 3166-1 codes and names
+pub const REGIONS: &[(&str, &str)] = &[
+    ("AR", "Argentina"),
+    ("AU", "Australia"),
+    ("AT", "Austria"),
+    ("BE", "Belgium"),
+    ("BR", "Brazil"),
+    ("CA", "Canada"),
+    ("CN", "China"),
+    ("CZ", "Czechia"),
+    ("DK", "Denmark"),
+    ("FI", "Finland"),
+    ("FR", "France"),
+    ("DE", "Germany"),
+    ("IN", "India"),
+    ("IE", "Ireland"),
+    ("IT", "Italy"),
+    ("JP", "Japan"),
+    ("MX", "Mexico"),
+    ("NL", "Netherlands"),
+    ("NO", "Norway"),
+    ("PL", "Poland"),
+    ("PT", "Portugal"),
+    ("KR", "South Korea"),
+    ("ES", "Spain"),
+    ("SE", "Sweden"),
+    ("CH", "Switzerland"),
+    ("TW", "Taiwan"),
+    ("TR", "Turkey"),
+    ("UA", "Ukraine"),
+    ("GB", "United Kingdom"),
+    ("US", "United States"),
+];
+
+/// A user's choice of alternative frontend for a service
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrontendSetting {
+    pub enabled: bool,
+    pub instance: String,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Settings {
@@ -22,10 +116,28 @@ pub struct Settings {
     pub remove_tracking: bool,
     pub bold_terms: bool,
     pub safesearch: SafeSearch,
+    /// Preferred language of results, one of [`LANGUAGES`], empty for any
+    #[serde(default)]
+    pub language: String,
+    /// Preferred region of results, one of [`REGIONS`], empty for any
+    #[serde(default)]
+    pub region: String,
     pub enable_widgets: bool,
+    /// Send results as providers answer instead of all at once
+    #[serde(default)]
+    pub stream_results: bool,
     pub show_full_path: bool,
     pub temperature_unit: String,
     pub timezone: String,
+    /// Alternative frontends to rewrite links to, by service
+    #[serde(default)]
+    pub frontends: HashMap<String, FrontendSetting>,
+    /// Choices for providers the user changed, by name
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderOptions>,
+    /// The user's own redirect bangs, URL templates by bang
+    #[serde(default)]
+    pub bangs: HashMap<String, String>,
 }
 
 impl Default for Settings {
@@ -39,19 +151,87 @@ impl Default for Settings {
             remove_tracking: true,
             bold_terms: true,
             safesearch: SafeSearch::default(),
+            language: String::new(),
+            region: String::new(),
             enable_widgets: true,
+            stream_results: false,
             show_full_path: false,
             temperature_unit: "C".to_string(),
             timezone: "UTC".to_string(),
+            frontends: HashMap::new(),
+            providers: HashMap::new(),
+            bangs: HashMap::new(),
         }
     }
 }
 
 impl Settings {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> SettingsBuilder {
         SettingsBuilder::default()
     }
 
+    /// Search options that follow from these settings
+    pub fn search_options(&self) -> SearchOptions {
+        let frontends = self
+            .frontends
+            .iter()
+            .filter(|(_, frontend)| frontend.enabled)
+            .map(|(service, frontend)| (service.clone(), frontend.instance.clone()))
+            .collect();
+
+        SearchOptions {
+            postprocessors: HashMap::from([("frontends".to_owned(), frontends)]),
+            providers: self.providers.clone(),
+            bangs: self.bangs.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply settings given as request parameters, like those of the settings form
+    ///
+    /// Only settings that change what a search returns are read. Anything
+    /// missing or invalid keeps its current value, and provider choices are
+    /// applied on top of the current ones.
+    pub fn with_overrides(mut self, params: &HashMap<String, String>) -> Self {
+        let flag = |key: &str| params.get(key).map(|v| v == "true");
+
+        if let Some(safesearch) = params.get("safesearch").and_then(|s| s.parse().ok()) {
+            self.safesearch = safesearch;
+        }
+        if let Some(language) = params
+            .get("language")
+            .filter(|l| l.is_empty() || LANGUAGES.iter().any(|(code, _)| code == l))
+        {
+            self.language = language.clone();
+        }
+        if let Some(region) = params
+            .get("region")
+            .filter(|r| r.is_empty() || REGIONS.iter().any(|(code, _)| code == r))
+        {
+            self.region = region.clone();
+        }
+        self.enable_widgets = flag("enable_widgets").unwrap_or(self.enable_widgets);
+        self.remove_tracking = flag("remove_tracking").unwrap_or(self.remove_tracking);
+        self.bold_terms = flag("bold_terms").unwrap_or(self.bold_terms);
+
+        for (name, choices) in provider_options(params) {
+            let current = self.providers.entry(name).or_default();
+            current.enabled = choices.enabled.or(current.enabled);
+            current.weight = choices.weight.or(current.weight);
+            current.extra.extend(choices.extra);
+        }
+        // Callers don't send the form's `provider.<name>.default` fields, an
+        // explicit choice here is always kept
+        for (field, value) in params {
+            if let Some(name) = field.strip_prefix("provider.").filter(|name| !name.contains('.')) {
+                self.providers.entry(name.to_owned()).or_default().enabled = Some(value == "true");
+            }
+        }
+
+        self
+    }
+
     pub fn to_cookies(&self) -> String {
         // Serialize settings to JSON and base64-encode to make it cookie-safe.
         let json = serde_json::to_string(self).unwrap();
@@ -102,10 +282,24 @@ impl Settings {
                 .and_then(|v| v.as_str())
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(defaults.safesearch),
+            language: json_value
+                .get("language")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or(defaults.language),
+            region: json_value
+                .get("region")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .unwrap_or(defaults.region),
             enable_widgets: json_value
                 .get("enable_widgets")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(defaults.enable_widgets),
+            stream_results: json_value
+                .get("stream_results")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(defaults.stream_results),
             show_full_path: json_value
                 .get("show_full_path")
                 .and_then(|v| v.as_bool())
@@ -120,6 +314,18 @@ impl Settings {
                 .and_then(|v| v.as_str())
                 .map(String::from)
                 .unwrap_or(defaults.timezone),
+            frontends: json_value
+                .get("frontends")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(defaults.frontends),
+            providers: json_value
+                .get("providers")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(defaults.providers),
+            bangs: json_value
+                .get("bangs")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or(defaults.bangs),
         }
     }
 }
@@ -134,10 +340,16 @@ pub struct SettingsBuilder {
     remove_tracking: Option<bool>,
     bold_terms: Option<bool>,
     safesearch: Option<SafeSearch>,
+    language: Option<String>,
+    region: Option<String>,
     enable_widgets: Option<bool>,
+    stream_results: Option<bool>,
     show_full_path: Option<bool>,
     temperature_unit: Option<String>,
     timezone: Option<String>,
+    frontends: Option<HashMap<String, FrontendSetting>>,
+    providers: Option<HashMap<String, ProviderOptions>>,
+    bangs: Option<HashMap<String, String>>,
 }
 
 impl SettingsBuilder {
@@ -181,11 +393,26 @@ impl SettingsBuilder {
         self
     }
 
+    pub fn language<S: Into<String>>(mut self, language: S) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn region<S: Into<String>>(mut self, region: S) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
     pub fn enable_widgets(mut self, enable_widgets: bool) -> Self {
         self.enable_widgets = Some(enable_widgets);
         self
     }
 
+    pub fn stream_results(mut self, stream_results: bool) -> Self {
+        self.stream_results = Some(stream_results);
+        self
+    }
+
     pub fn show_full_path(mut self, show_full_path: bool) -> Self {
         self.show_full_path = Some(show_full_path);
         self
@@ -201,6 +428,21 @@ impl SettingsBuilder {
         self
     }
 
+    pub fn frontends(mut self, frontends: HashMap<String, FrontendSetting>) -> Self {
+        self.frontends = Some(frontends);
+        self
+    }
+
+    pub fn providers(mut self, providers: HashMap<String, ProviderOptions>) -> Self {
+        self.providers = Some(providers);
+        self
+    }
+
+    pub fn bangs(mut self, bangs: HashMap<String, String>) -> Self {
+        self.bangs = Some(bangs);
+        self
+    }
+
     pub fn build(self) -> Settings {
         let defaults = Settings::default();
         Settings {
@@ -212,10 +454,16 @@ impl SettingsBuilder {
             remove_tracking: self.remove_tracking.unwrap_or(defaults.remove_tracking),
             bold_terms: self.bold_terms.unwrap_or(defaults.bold_terms),
             safesearch: self.safesearch.unwrap_or(defaults.safesearch),
+            language: self.language.unwrap_or(defaults.language),
+            region: self.region.unwrap_or(defaults.region),
             enable_widgets: self.enable_widgets.unwrap_or(defaults.enable_widgets),
+            stream_results: self.stream_results.unwrap_or(defaults.stream_results),
             show_full_path: self.show_full_path.unwrap_or(defaults.show_full_path),
             temperature_unit: self.temperature_unit.unwrap_or(defaults.temperature_unit),
             timezone: self.timezone.unwrap_or(defaults.timezone),
+            frontends: self.frontends.unwrap_or(defaults.frontends),
+            providers: self.providers.unwrap_or(defaults.providers),
+            bangs: self.bangs.unwrap_or(defaults.bangs),
         }
     }
 }
@@ -224,10 +472,12 @@ impl From<CookieJar> for Settings {
     fn from(jar: CookieJar) -> Self {
         if let Some(cookie) = jar.get("settings") {
             debug!("Loading settings from cookie");
-            if let Ok(decoded) = general_purpose::STANDARD.decode(cookie.value()) {
-                if let Ok(settings) = serde_json::from_slice::<Settings>(&decoded) {
-                    return settings;
-                }
+            if let Some(settings) = general_purpose::STANDARD
+                .decode(cookie.value())
+                .ok()
+                .and_then(|decoded| serde_json::from_slice::<Settings>(&decoded).ok())
+            {
+                return settings;
             }
             warn!("Failed to decode settings cookie, using defaults");
         }
@@ -305,12 +555,32 @@ pub async fn update_settings(Form(params): Form<HashMap<String, String>>) -> imp
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(defaults.safesearch),
         )
+        .language(
+            params
+                .get("language")
+                .filter(|l| LANGUAGES.iter().any(|(code, _)| code == l))
+                .cloned()
+                .unwrap_or(defaults.language),
+        )
+        .region(
+            params
+                .get("region")
+                .filter(|r| REGIONS.iter().any(|(code, _)| code == r))
+                .cloned()
+                .unwrap_or(defaults.region),
+        )
         .enable_widgets(
             params
                 .get("enable_widgets")
                 .map(|v| v == "true")
                 .unwrap_or(defaults.enable_widgets),
         )
+        .stream_results(
+            params
+                .get("stream_results")
+                .map(|v| v == "true")
+                .unwrap_or(defaults.stream_results),
+        )
         .show_full_path(
             params
                 .get("show_full_path")
@@ -331,6 +601,33 @@ pub async fn update_settings(Form(params): Form<HashMap<String, String>>) -> imp
                 .filter(|s| !s.is_empty())
                 .unwrap_or(defaults.timezone),
         )
+        .frontends(
+            FRONTENDS
+                .iter()
+                .map(|frontend| {
+                    let enabled = params
+                        .get(&format!("frontend_{}", frontend.service))
+                        .is_some_and(|v| v == "true");
+                    // Only take instances we can actually link to
+                    let instance = params
+                        .get(&format!("frontend_{}_instance", frontend.service))
+                        .filter(|i| {
+                            Url::parse(i).is_ok_and(|url| matches!(url.scheme(), "http" | "https"))
+                        })
+                        .cloned()
+                        .unwrap_or_else(|| frontend.default_instance.to_owned());
+
+                    (frontend.service.to_owned(), FrontendSetting { enabled, instance })
+                })
+                .collect(),
+        )
+        .providers(provider_options(&params))
+        .bangs(
+            params
+                .get("bangs")
+                .map(|bangs| parse_bangs(bangs))
+                .unwrap_or(defaults.bangs),
+        )
         .build();
 
     let cookie = settings.to_cookies();
@@ -342,6 +639,68 @@ pub async fn update_settings(Form(params): Form<HashMap<String, String>>) -> imp
         .unwrap()
 }
 
+/// Read provider choices from the settings form
+///
+/// Fields are named `provider.<name>`, `provider.<name>.weight` and
+/// `provider.<name>.extra.<option>`. Only choices that differ from the
+/// instance's defaults are kept, so later changes to those still apply.
+fn provider_options(params: &HashMap<String, String>) -> HashMap<String, ProviderOptions> {
+    let mut providers: HashMap<String, ProviderOptions> = HashMap::new();
+
+    for (field, value) in params {
+        let Some(field) = field.strip_prefix("provider.") else {
+            continue;
+        };
+        let (name, rest) = field.split_once('.').unwrap_or((field, ""));
+
+        match rest.split_once('.').unwrap_or((rest, "")) {
+            ("", _) => {
+                let enabled = value == "true";
+                let default = params.get(&format!("provider.{name}.default"));
+                if default.is_some_and(|d| (d == "true") != enabled) {
+                    providers.entry(name.to_owned()).or_default().enabled = Some(enabled);
+                }
+            }
+            ("weight", _) => {
+                if let Some(weight) = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite() && *w >= 0.0 && *w != 1.0)
+                {
+                    providers.entry(name.to_owned()).or_default().weight = Some(weight);
+                }
+            }
+            ("extra", option) if !option.is_empty() && !value.is_empty() => {
+                providers
+                    .entry(name.to_owned())
+                    .or_default()
+                    .extra
+                    .insert(option.to_owned(), value.clone());
+            }
+            _ => {}
+        }
+    }
+
+    providers
+}
+
+/// Read custom bangs from the settings form, one `!bang url` per line
+///
+/// Lines without a usable URL template are left out.
+fn parse_bangs(bangs: &str) -> HashMap<String, String> {
+    bangs
+        .lines()
+        .filter_map(|line| {
+            let (bang, template) = line.trim().split_once(char::is_whitespace)?;
+            let bang = bang.trim_start_matches('!').to_lowercase();
+            let template = template.trim();
+
+            (!bang.is_empty() && bangs::is_valid_template(template))
+                .then(|| (bang, template.to_owned()))
+        })
+        .collect()
+}
+
 // Modified export_settings endpoint to allow download via query parameter.
 pub async fn export_settings(
     Extension(settings): Extension<Settings>,
@@ -1,21 +1,23 @@
 use log::{debug, error, info};
 use reqwest::Client;
-use std::{path::PathBuf, process, sync::Arc};
+use std::{collections::HashMap, convert::Infallible, path::PathBuf, process, sync::Arc};
 
 use axum::http::{StatusCode, header};
 use axum::{
-    Router,
+    Json, Router,
+    body::Body,
     extract::{Extension, Query, State},
     middleware,
     response::{Html, IntoResponse, Redirect, Response},
     routing::{get, post},
 };
 use once_cell::sync::Lazy;
-use searched::{Error, Kind, SearchResult};
-use serde::Deserialize;
+use searched::{Kind, SearchResponse, SearchResult};
+use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, mpsc};
 use tokio::try_join;
+use tokio_stream::wrappers::ReceiverStream;
 use tower_http::services::ServeDir;
 
 use crate::modules::favicon::favicon;
@@ -24,7 +26,7 @@ use crate::{
     AppState,
     modules::{text_matcher::highlight_text, url_cleaner},
     settings::{
-        Settings, export_settings, import_settings, import_settings_form, settings_middleware,
+        FRONTENDS, LANGUAGES, REGIONS, Settings, export_settings, import_settings, import_settings_form, settings_middleware,
         update_settings,
     },
     widgets,
@@ -36,7 +38,7 @@ pub static TERA: Lazy<Arc<RwLock<Tera>>> = Lazy::new(|| {
     Arc::new(RwLock::new(tera))
 });
 
-const MOTD: &'static [&'static str] = &[
+const MOTD: &[&str] = &[
     "<i>Blazingly</i> fast",
     "RIIR",
     "\"It's not a cult\"",
@@ -65,25 +67,69 @@ fn get_motd() -> &'static str {
     MOTD[fastrand::usize(..MOTD.len())]
 }
 
+/// A provider that failed, as shown on the results page
+#[derive(Debug, Serialize)]
+struct ProviderNotice {
+    provider: String,
+    message: String,
+}
+
+/// A spelling correction, linking to a search for it
+#[derive(Debug, Serialize)]
+struct CorrectionLink {
+    text: String,
+    href: String,
+}
+
+/// A provider as shown on the settings page
+#[derive(Debug, Serialize)]
+struct ProviderEntry {
+    id: String,
+    name: String,
+    description: String,
+    /// Whether the instance asks this provider unless the user says otherwise
+    default_enabled: bool,
+    enabled: bool,
+    weight: f64,
+    options: Vec<ProviderOptionEntry>,
+}
+
+/// An engine option users can change, as shown on the settings page
+#[derive(Debug, Serialize)]
+struct ProviderOptionEntry {
+    key: String,
+    name: String,
+    choices: Vec<String>,
+    default: String,
+    /// The user's value, empty for the default
+    value: String,
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct SearchParams {
     q: Option<String>,
     k: Option<Kind>,
+    /// Safe search, overrides the user's setting
     s: Option<String>,
     p: Option<usize>,
+    /// Time range, anything else means any time
+    t: Option<String>,
+    nocache: Option<bool>,
+    /// Merger to use instead of the configured one
+    merger: Option<String>,
+    /// Ranker to use instead of the configured one
+    ranker: Option<String>,
 }
 
 fn create_tera() -> Tera {
     info!("Loading Tera templates from views/**/*");
-    let tera = match Tera::new("views/**/*") {
+    match Tera::new("views/**/*") {
         Ok(t) => t,
         Err(e) => {
             error!("Template parsing error(s): {}", e);
             process::exit(1);
         }
-    };
-
-    tera
+    }
 }
 
 pub async fn index(Extension(settings): Extension<Settings>) -> impl IntoResponse {
@@ -110,6 +156,24 @@ async fn detect_widget_async(
     })
 }
 
+/// The query a search request asks for
+fn search_query(q: String, params: &SearchParams, settings: &Settings) -> searched::Query {
+    searched::Query {
+        query: q,
+        kind: params.k.unwrap_or_default(),
+        page: params.p.unwrap_or(1),
+        safe: params
+            .s
+            .as_deref()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(settings.safesearch),
+        no_cache: params.nocache.unwrap_or_default(),
+        language: Some(settings.language.clone()).filter(|l| !l.is_empty()),
+        region: Some(settings.region.clone()).filter(|r| !r.is_empty()),
+        time_range: params.t.as_deref().and_then(|t| t.parse().ok()),
+    }
+}
+
 pub async fn search_results(
     Extension(settings): Extension<Settings>,
     Query(params): Query<SearchParams>,
@@ -120,37 +184,51 @@ pub async fn search_results(
     let mut context = Context::new();
     context.insert("settings", &settings);
 
-    if let Some(q) = params.q {
+    if let Some(q) = params.q.clone() {
         let kind = params.k.unwrap_or_default();
-        let query = searched::Query {
-            query: q.clone(),
-            kind: kind.clone(),
-            page: params.p.unwrap_or(1),
-            safe: settings.safesearch.clone(),
-            ..Default::default()
+        let query = search_query(q.clone(), &params, &settings);
+
+        let options = searched::SearchOptions {
+            merger: params.merger,
+            ranker: params.ranker,
+            ..settings.search_options()
         };
+        let providers = st.eng.providers_for(kind, &options);
+        if providers.is_empty() {
+            context.insert("no_providers", &true);
+        }
+
+        if settings.stream_results {
+            return stream_results(context, settings, st, query, providers, options).await;
+        }
 
         let search_start = std::time::Instant::now();
 
         // Run widget detection and search concurrently with proper Result handling
-        let (widget_option, mut search_results) = try_join!(
+        let (widget_option, search_response) = try_join!(
             detect_widget_async(&q, &st.client, &st.db, &settings),
             //async { Ok(st.eng.search(query.clone(), params.s.clone().unwrap_or("duckduckgo".to_string())).await.unwrap()) as Result<_, ()> }
-            async { Ok(st.eng.search(query.clone(), vec!["duckduckgo".to_owned(), "stract".to_owned(), "qwant".to_owned(), "mojeek".to_owned(), "ask".to_owned()]).await.unwrap()) as Result<_, ()> }
+            async { Ok(st.eng.search(query.clone(), providers, &options).await) as Result<_, ()> }
         )
-        .unwrap_or((None, Vec::<SearchResult>::new()));
+        .unwrap_or((None, Ok(SearchResponse::default())));
 
-        // Process search results
-        for result in &mut search_results {
-            if settings.bold_terms {
-                result.title = highlight_text(&result.title, &q);
+        let search_response = search_response.unwrap_or_else(|err: searched::Error| {
+            error!("search for {:?} failed: {err}", query.query);
+            context.insert("search_error", &err.to_string());
+            SearchResponse {
+                query: query.clone(),
+                ..Default::default()
             }
+        });
 
-            if settings.remove_tracking {
-                result.url = url_cleaner::clean_url(result.url.clone());
-            }
+        if let Some(redirect) = search_response.redirect {
+            return Redirect::to(&redirect).into_response();
         }
 
+        // Preprocessors may have rewritten the query
+        insert_query(&mut context, &search_response.query, &search_response.bang);
+        insert_response(&mut context, search_response, &settings, &st, &q);
+
         let search_time = search_start.elapsed().as_millis();
         debug!("Search completed in {}ms", search_time);
 
@@ -159,21 +237,6 @@ pub async fn search_results(
             context.insert("widget", &widget);
         }
 
-        // Use the Kind's string value for the template
-        match kind {
-            Kind::General => context.insert("kind", "sear"),
-            Kind::Images => context.insert("kind", "imgs"),
-            Kind::Videos => context.insert("kind", "vids"),
-            Kind::News => context.insert("kind", "news"),
-            Kind::Maps => context.insert("kind", "maps"),
-            Kind::Wiki => context.insert("kind", "wiki"),
-            Kind::QuestionAnswer => context.insert("kind", "qans"),
-            Kind::Documentation => context.insert("kind", "docs"),
-            Kind::Papers => context.insert("kind", "pprs"),
-        }
-
-        context.insert("query", &query);
-        context.insert("results", &search_results);
         context.insert("search_time", &search_time);
 
         let rendered = TERA.read().await.render("results.tera", &context).unwrap();
@@ -183,14 +246,525 @@ pub async fn search_results(
     }
 }
 
-pub async fn settings_page(Extension(settings): Extension<Settings>) -> impl IntoResponse {
+/// Render the results page as a stream, with the results again after
+/// every provider that answers
+///
+/// The page up to the results is sent right away, the widget follows once
+/// it's ready. Every update is appended after the previous ones and CSS only
+/// shows the latest, so this works without JavaScript.
+async fn stream_results(
+    mut context: Context,
+    settings: Settings,
+    st: AppState,
+    query: searched::Query,
+    providers: Vec<String>,
+    options: searched::SearchOptions,
+) -> Response {
+    let search_start = std::time::Instant::now();
+    let q = query.query.clone();
+
+    let mut stream = match st.eng.search_stream(query.clone(), providers, options).await {
+        Ok(stream) => stream,
+        Err(err) => {
+            error!("search for {:?} failed: {err}", query.query);
+            context.insert("search_error", &err.to_string());
+            insert_query(&mut context, &query, &None);
+            insert_response(&mut context, SearchResponse::default(), &settings, &st, &q);
+            context.insert("search_time", &search_start.elapsed().as_millis());
+
+            let rendered = TERA.read().await.render("results.tera", &context).unwrap();
+            return Html(rendered).into_response();
+        }
+    };
+
+    if let Some(redirect) = stream.redirect {
+        return Redirect::to(&redirect).into_response();
+    }
+
+    insert_query(&mut context, &stream.query, &stream.bang);
+
+    let mut page_context = context.clone();
+    page_context.insert("streaming", &true);
+    let page = TERA.read().await.render("results.tera", &page_context).unwrap();
+    let (head, rest) = split_at(page, "<!-- widget -->");
+    let (middle, tail) = split_at(rest, "<!-- results -->");
+
+    let (tx, rx) = mpsc::channel::<Result<String, Infallible>>(4);
+    tokio::spawn(async move {
+        if tx.send(Ok(head)).await.is_err() {
+            return;
+        }
+
+        // Some widgets make requests of their own, the search goes on meanwhile
+        if let Ok(Some(widget)) = detect_widget_async(&q, &st.client, &st.db, &settings).await {
+            let mut context = context.clone();
+            context.insert("widget", &widget);
+            let rendered = TERA
+                .read()
+                .await
+                .render("components/widget.tera", &context)
+                .unwrap();
+            if tx.send(Ok(rendered)).await.is_err() {
+                return;
+            }
+        }
+        if tx.send(Ok(middle)).await.is_err() {
+            return;
+        }
+
+        while let Some(update) = stream.updates.recv().await {
+            let mut context = context.clone();
+            let response = update.unwrap_or_else(|err| {
+                error!("search for {q:?} failed: {err}");
+                context.insert("search_error", &err.to_string());
+                SearchResponse::default()
+            });
+            insert_response(&mut context, response, &settings, &st, &q);
+            context.insert("search_time", &search_start.elapsed().as_millis());
+
+            let rendered = TERA
+                .read()
+                .await
+                .render("components/result_list.tera", &context)
+                .unwrap();
+            // Dropping the stream stops the search once the user is gone
+            if tx
+                .send(Ok(format!("<div class=\"snapshot\">{rendered}</div>")))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        debug!("Streamed search completed in {}ms", search_start.elapsed().as_millis());
+        let _ = tx.send(Ok(tail)).await;
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from_stream(ReceiverStream::new(rx)))
+        .unwrap()
+}
+
+/// Split a rendered page at a marker comment, dropping the marker
+fn split_at(page: String, marker: &str) -> (String, String) {
+    page.split_once(marker)
+        .map(|(head, tail)| (head.to_owned(), tail.to_owned()))
+        .unwrap_or((page, String::new()))
+}
+
+/// The value of `k` that searches a kind
+fn kind_id(kind: Kind) -> &'static str {
+    match kind {
+        Kind::General => "sear",
+        Kind::Images => "imgs",
+        Kind::Videos => "vids",
+        Kind::News => "news",
+        Kind::Maps => "maps",
+        Kind::Wiki => "wiki",
+        Kind::QuestionAnswer => "qans",
+        Kind::Documentation => "docs",
+        Kind::Papers => "pprs",
+    }
+}
+
+/// Add the query and its kind for the page header
+///
+/// A bang that limited the search stays in the query, so the next page
+/// and other kinds ask the same provider.
+fn insert_query(context: &mut Context, query: &searched::Query, bang: &Option<String>) {
+    // Use the Kind's string value for the template
+    context.insert("kind", kind_id(query.kind));
+
+    match bang {
+        Some(bang) => {
+            let mut query = query.clone();
+            query.query = format!("!{bang} {}", query.query);
+            context.insert("query", &query);
+        }
+        None => context.insert("query", query),
+    }
+}
+
+/// Add the results and provider notices of a search response
+fn insert_response(
+    context: &mut Context,
+    search_response: SearchResponse,
+    settings: &Settings,
+    st: &AppState,
+    q: &str,
+) {
+    let mut search_results = search_response.results;
+
+    // Show providers by their human readable names
+    let providers = st.eng.providers();
+    let provider_name = |id: &String| providers.0.get(id).map_or(id.clone(), |cfg| cfg.name.clone());
+    let provider_errors = search_response
+        .errors
+        .iter()
+        .map(|e| ProviderNotice {
+            provider: provider_name(&e.provider),
+            message: e.error.to_string(),
+        })
+        .collect::<Vec<_>>();
+    let suspended = search_response
+        .suspended
+        .iter()
+        .map(provider_name)
+        .collect::<Vec<_>>();
+    let pending = search_response
+        .pending
+        .iter()
+        .map(provider_name)
+        .collect::<Vec<_>>();
+
+    // Offer the correction most providers agree on, keeping the bang
+    if let Some(correction) = search_response.corrections.first() {
+        let corrected = match &search_response.bang {
+            Some(bang) => format!("!{bang} {correction}"),
+            None => correction.clone(),
+        };
+        let mut href = format!(
+            "/search?q={}&k={}",
+            urlencoding::encode(&corrected),
+            kind_id(search_response.query.kind)
+        );
+        if let Some(time_range) = search_response.query.time_range {
+            href.push_str(&format!("&t={time_range}"));
+        }
+        context.insert(
+            "correction",
+            &CorrectionLink {
+                text: correction.clone(),
+                href,
+            },
+        );
+    }
+
+    // Process search results
+    for result in &mut search_results {
+        if settings.bold_terms {
+            result.title = highlight_text(&result.title, q);
+        }
+
+        if settings.remove_tracking {
+            result.url = url_cleaner::clean_url(result.url.clone());
+        }
+    }
+
+    context.insert("results", &search_results);
+    context.insert("provider_errors", &provider_errors);
+    context.insert("suspended", &suspended);
+    context.insert("pending", &pending);
+    context.insert("warnings", &search_response.warnings);
+}
+
+/// A search as `/api/search` returns it
+#[derive(Debug, Serialize)]
+struct ApiSearch {
+    /// The query that was searched, after preprocessing
+    query: searched::Query,
+    /// The bang used, without its `!`
+    bang: Option<String>,
+    /// Where the web interface would send the user instead of searching
+    redirect: Option<String>,
+    results: Vec<SearchResult>,
+    /// Spelling corrections providers suggested, the most agreed on first
+    corrections: Vec<String>,
+    /// Other searches providers suggested
+    related: Vec<String>,
+    /// The most results any provider estimated having
+    total: Option<u64>,
+    /// Every provider asked, plus any skipped for failing
+    providers: Vec<ApiProvider>,
+    /// Mergers and rankers that failed, the native ones were used instead
+    warnings: Vec<String>,
+    widget: Option<widgets::Widget>,
+    page: ApiPage,
+    /// Milliseconds the whole search took
+    search_time: u128,
+}
+
+/// How a provider did in a search
+#[derive(Debug, Serialize)]
+struct ApiProvider {
+    id: String,
+    name: String,
+    /// Milliseconds it took to answer, unset if it didn't
+    time: Option<u64>,
+    error: Option<String>,
+    /// Skipped because it has been failing
+    suspended: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiPage {
+    current: usize,
+    previous: Option<usize>,
+    /// Unset when this page had no results
+    next: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiError {
+    error: String,
+}
+
+/// Search and return the results as JSON
+///
+/// Takes the same parameters as `/search`. Fields of the settings form,
+/// like `language` or `provider.<name>`, override the user's settings for
+/// this search.
+pub async fn api_search(
+    Extension(settings): Extension<Settings>,
+    Query(params): Query<SearchParams>,
+    Query(overrides): Query<HashMap<String, String>>,
+    State(st): State<AppState>,
+) -> Response {
+    let Some(q) = params.q.clone().filter(|q| !q.trim().is_empty()) else {
+        let error = ApiError {
+            error: "missing query, search with ?q=...".to_owned(),
+        };
+        return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+    };
+
+    let settings = settings.with_overrides(&overrides);
+    let query = search_query(q.clone(), &params, &settings);
+    let options = searched::SearchOptions {
+        merger: params.merger,
+        ranker: params.ranker,
+        ..settings.search_options()
+    };
+    let providers = st.eng.providers_for(query.kind, &options);
+
+    let search_start = std::time::Instant::now();
+    let (widget, response) = tokio::join!(
+        detect_widget_async(&q, &st.client, &st.db, &settings),
+        st.eng.search(query.clone(), providers, &options)
+    );
+    let response = match response {
+        Ok(response) => response,
+        Err(err) => {
+            error!("search for {:?} failed: {err}", query.query);
+            // Asking for a merger or ranker that doesn't exist is on the caller
+            let status = match err {
+                searched::Error::MergerNotFound(_) | searched::Error::RankerNotFound(_) => {
+                    StatusCode::BAD_REQUEST
+                }
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let error = ApiError {
+                error: err.to_string(),
+            };
+            return (status, Json(error)).into_response();
+        }
+    };
+
+    let provider_cfgs = st.eng.providers();
+    let provider_name = |id: &String| provider_cfgs.0.get(id).map_or(id.clone(), |cfg| cfg.name.clone());
+    let mut provider_reports = response
+        .queried
+        .iter()
+        .map(|id| ApiProvider {
+            id: id.clone(),
+            name: provider_name(id),
+            time: response.timings.get(id).copied(),
+            error: response
+                .errors
+                .iter()
+                .find(|e| &e.provider == id)
+                .map(|e| e.error.to_string()),
+            suspended: false,
+        })
+        .collect::<Vec<_>>();
+    provider_reports.extend(response.suspended.iter().map(|id| ApiProvider {
+        id: id.clone(),
+        name: provider_name(id),
+        time: None,
+        error: None,
+        suspended: true,
+    }));
+
+    let mut results = response.results;
+    if settings.remove_tracking {
+        for result in &mut results {
+            result.url = url_cleaner::clean_url(result.url.clone());
+        }
+    }
+
+    let page = response.query.page;
+    Json(ApiSearch {
+        page: ApiPage {
+            current: page,
+            previous: page.checked_sub(1).filter(|p| *p > 0),
+            next: (!results.is_empty()).then_some(page + 1),
+        },
+        query: response.query,
+        bang: response.bang,
+        redirect: response.redirect,
+        results,
+        corrections: response.corrections,
+        related: response.related,
+        total: response.total,
+        providers: provider_reports,
+        warnings: response.warnings,
+        widget: widget.unwrap_or_default(),
+        search_time: search_start.elapsed().as_millis(),
+    })
+    .into_response()
+}
+
+pub async fn settings_page(
+    Extension(settings): Extension<Settings>,
+    State(st): State<AppState>,
+) -> impl IntoResponse {
     let mut context = Context::new();
     context.insert("settings", &settings);
+    context.insert("frontends", FRONTENDS);
+    context.insert("languages", LANGUAGES);
+    context.insert("regions", REGIONS);
+    context.insert("providers", &provider_entries(&settings, &st));
 
     let rendered = TERA.read().await.render("settings.tera", &context).unwrap();
     Html(rendered).into_response()
 }
 
+/// Every provider with the user's choices for it, sorted by name
+fn provider_entries(settings: &Settings, st: &AppState) -> Vec<ProviderEntry> {
+    let providers = st.eng.providers();
+    let mut entries = providers
+        .0
+        .iter()
+        .map(|(id, cfg)| {
+            let chosen = settings.providers.get(id).cloned().unwrap_or_default();
+            let default_enabled = cfg
+                .kinds
+                .iter()
+                .any(|kind| st.eng.default_providers(*kind).contains(id));
+
+            let mut options = cfg
+                .settings
+                .iter()
+                .map(|(key, setting)| ProviderOptionEntry {
+                    key: key.clone(),
+                    name: setting.name.clone(),
+                    choices: setting.choices.clone(),
+                    default: cfg
+                        .extra
+                        .as_ref()
+                        .and_then(|extra| extra.get(key))
+                        .and_then(|value| value.as_str())
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    value: chosen.extra.get(key).cloned().unwrap_or_default(),
+                })
+                .collect::<Vec<_>>();
+            options.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+
+            ProviderEntry {
+                id: id.clone(),
+                name: cfg.name.clone(),
+                description: cfg.description.clone(),
+                default_enabled,
+                enabled: chosen.enabled.unwrap_or(default_enabled),
+                weight: chosen.weight.unwrap_or(1.0),
+                options,
+            }
+        })
+        .collect::<Vec<_>>();
+    entries.sort_unstable_by_key(|entry| entry.name.to_lowercase());
+
+    entries
+}
+
+/// A bang as listed on the bangs page
+#[derive(Debug, Serialize)]
+struct BangEntry {
+    bang: String,
+    name: String,
+    /// Where it sends the user, or the provider it asks
+    target: String,
+}
+
+/// Bangs listed under one heading
+#[derive(Debug, Serialize)]
+struct BangGroup {
+    title: &'static str,
+    bangs: Vec<BangEntry>,
+}
+
+pub async fn bangs_page(
+    Extension(settings): Extension<Settings>,
+    State(st): State<AppState>,
+) -> impl IntoResponse {
+    let sorted = |mut bangs: Vec<BangEntry>| {
+        bangs.sort_unstable_by(|a, b| a.bang.cmp(&b.bang));
+        bangs
+    };
+
+    let user = settings
+        .bangs
+        .iter()
+        .map(|(bang, url)| BangEntry {
+            bang: bang.clone(),
+            name: String::new(),
+            target: url.clone(),
+        })
+        .collect();
+
+    // Bangs shadowed by ones that are looked up first never fire
+    let bangs_cfg = st.eng.bangs();
+    let instance = bangs_cfg
+        .0
+        .iter()
+        .filter(|(bang, _)| !settings.bangs.contains_key(*bang))
+        .map(|(bang, cfg)| BangEntry {
+            bang: bang.clone(),
+            name: cfg.name.clone(),
+            target: cfg
+                .url
+                .clone()
+                .or_else(|| cfg.provider.as_ref().map(|p| format!("Only asks {p}")))
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let providers_cfg = st.eng.providers();
+    let providers = providers_cfg
+        .0
+        .iter()
+        .filter(|(id, _)| !settings.bangs.contains_key(*id) && !bangs_cfg.0.contains_key(*id))
+        .map(|(id, cfg)| BangEntry {
+            bang: id.clone(),
+            name: cfg.name.clone(),
+            target: format!("Only asks {id}"),
+        })
+        .collect();
+
+    let groups = [
+        BangGroup {
+            title: "Your bangs",
+            bangs: sorted(user),
+        },
+        BangGroup {
+            title: "Shortcuts",
+            bangs: sorted(instance),
+        },
+        BangGroup {
+            title: "Providers",
+            bangs: sorted(providers),
+        },
+    ];
+
+    let mut context = Context::new();
+    context.insert("settings", &settings);
+    context.insert("groups", &groups);
+
+    let rendered = TERA.read().await.render("bangs.tera", &context).unwrap();
+    Html(rendered).into_response()
+}
+
 pub async fn about_page(Extension(settings): Extension<Settings>) -> impl IntoResponse {
     let mut context = Context::new();
     context.insert("settings", &settings);
@@ -198,6 +772,37 @@ pub async fn about_page(Extension(settings): Extension<Settings>) -> impl IntoRe
     Html(rendered).into_response()
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SuggestParams {
+    q: Option<String>,
+}
+
+/// Complete a partly typed query, in the OpenSearch suggestions format
+///
+/// That's the query followed by a list of completions, which is what
+/// browsers ask for when searched is their search engine.
+pub async fn suggest(
+    Extension(settings): Extension<Settings>,
+    Query(params): Query<SuggestParams>,
+    State(st): State<AppState>,
+) -> impl IntoResponse {
+    let q = params.q.unwrap_or_default();
+    let query = searched::Query {
+        query: q.clone(),
+        page: 1,
+        safe: settings.safesearch,
+        language: Some(settings.language.clone()).filter(|l| !l.is_empty()),
+        region: Some(settings.region.clone()).filter(|r| !r.is_empty()),
+        ..Default::default()
+    };
+    let suggestions = st.eng.suggest(query).await;
+
+    (
+        [(header::CONTENT_TYPE, "application/x-suggestions+json")],
+        serde_json::to_string(&(q, suggestions)).unwrap(),
+    )
+}
+
 pub async fn opensearch() -> impl IntoResponse {
     let xml = include_str!("../static/opensearch.xml");
     Response::builder()
@@ -215,14 +820,17 @@ pub fn router() -> Router<AppState> {
     Router::new()
         .route("/", get(index))
         .route("/search", get(search_results))
+        .route("/api/search", get(api_search))
         .route("/settings", get(settings_page))
         .route("/settings/update", post(update_settings))
         .route("/settings/export", get(export_settings))
         .route("/settings/import", post(import_settings))
         .route("/settings/import_form", post(import_settings_form))
         .route("/about", get(about_page))
+        .route("/bangs", get(bangs_page))
         .route("/favicon", get(favicon))
         .route("/image", get(proxy_image))
+        .route("/suggest", get(suggest))
         .route("/opensearch.xml", get(opensearch))
         .fallback_service(ServeDir::new(PathBuf::from("static")))
         .layer(middleware::from_fn(settings_middleware))
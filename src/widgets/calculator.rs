@@ -39,10 +39,8 @@ impl Calculator {
                 result: "".to_string(),
             });
         }
-        let explicit_conversion = match caps.name("prefix") {
-            Some(m) if m.as_str().to_lowercase() == "convert" => true,
-            _ => false,
-        };
+        let explicit_conversion =
+            matches!(caps.name("prefix"), Some(m) if m.as_str().to_lowercase() == "convert");
         // Only accept if expression starts with a digit, math symbol, or contains "to"
         let first = expr.chars().next()?;
         if !first.is_ascii_digit() && !"(-+.".contains(first) && !expr.contains("to") {
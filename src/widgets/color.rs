@@ -78,11 +78,11 @@ impl Color {
         let h = hsla[0] as f64;
         let s = hsla[1] as f64;
         let l = hsla[2] as f64;
-        let a2 = hsla[3] as f32;
+        let a2 = hsla[3];
 
         Some(Color {
             original_input: color_str.to_string(),
-            hex: color.to_hex_string(),
+            hex: color.to_css_hex(),
             rgb: (r, g, b),
             rgba: (r, g, b, a),
             hsl: (h, s * 100.0, l * 100.0),
@@ -80,8 +80,6 @@ impl Dictionary {
             "antonyms"
         } else if query.contains("meaning") {
             "meaning"
-        } else if query.contains("definition") {
-            "definition"
         } else {
             "definition" // default
         };
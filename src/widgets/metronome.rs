@@ -20,7 +20,7 @@ impl Metronome {
                 .name("bpm")
                 .or_else(|| caps.name("bpm2"))
                 .and_then(|m| m.as_str().parse::<u32>().ok())
-                .filter(|&bpm| bpm >= 30 && bpm <= 250);
+                .filter(|&bpm| (30..=250).contains(&bpm));
 
             return Some(Metronome { initial_bpm: bpm });
         }
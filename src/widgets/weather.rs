@@ -284,7 +284,7 @@ impl Weather {
             .iter()
             .zip(weather.hourly.temperature_2m.iter())
             .take(24)
-            .map(|(time, temp)| {
+            .filter_map(|(time, temp)| {
                 let hour = time
                     .split('T')
                     .nth(1)?
@@ -297,7 +297,6 @@ impl Weather {
                     temperature: *temp,
                 })
             })
-            .flatten()
             .collect();
 
         Some(Weather {
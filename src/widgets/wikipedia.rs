@@ -141,8 +141,8 @@ impl Wikipedia {
                             alternatives: if is_disambiguation {
                                 page.links
                                     .iter()
-                                    .filter_map(|link| {
-                                        Some(Alternative {
+                                    .map(|link| {
+                                        Alternative {
                                             title: link.title.clone(),
                                             description: link
                                                 .description
@@ -152,7 +152,7 @@ impl Wikipedia {
                                                 "https://en.wikipedia.org/wiki/{}",
                                                 urlencoding::encode(&link.title)
                                             ),
-                                        })
+                                        }
                                     })
                                     .collect()
                             } else {
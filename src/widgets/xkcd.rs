@@ -73,15 +73,15 @@ impl Xkcd {
                             }
                         }
                         let _ = xkcd_cache.insert(key.as_bytes(), bincode::serialize(&comic).ok()?);
-                        return Some(comic);
+                        Some(comic)
                     } else {
-                        return Some(Xkcd::error("Failed to parse comic data"));
+                        Some(Xkcd::error("Failed to parse comic data"))
                     }
                 } else {
-                    return Some(Xkcd::error("Comic not found"));
+                    Some(Xkcd::error("Comic not found"))
                 }
             }
-            Err(_) => return Some(Xkcd::error("Failed to fetch comic")),
+            Err(_) => Some(Xkcd::error("Failed to fetch comic")),
         }
     }
 
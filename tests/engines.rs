@@ -0,0 +1,97 @@
+//! Engines run against recorded upstream responses
+//!
+//! Fixtures live in `tests/fixtures/<provider>`, along with what each engine
+//! is expected to make of them in `expected*.json`. `SEARCHED_FIXTURES` changes what the
+//! tests do:
+//!
+//! - unset: replay fixtures and compare against the expected results
+//! - `record`: ask the real upstreams, saving their responses and results
+//! - `update`: replay fixtures and save the results as the expected ones
+//!
+//! Nothing has been recorded yet, so the tests are ignored. Record fixtures
+//! with `SEARCHED_FIXTURES=record cargo test --test engines -- --ignored`
+//! where the upstreams can be reached, then drop the `#[ignore]` of every
+//! test they cover.
+
+use std::{fs, path::Path};
+
+use searched::{
+    ProviderResponse, Query,
+    config::Config,
+    http_client,
+    lua_support::{FixtureStore, PluginEngine, Transport},
+};
+
+const FIXTURES: &str = "tests/fixtures";
+
+async fn check(provider: &str, query: &str, expected: &str) {
+    let mode = std::env::var("SEARCHED_FIXTURES").unwrap_or_default();
+    let store = FixtureStore::new(FIXTURES);
+    let transport = match mode.as_str() {
+        "record" => Transport::Record(http_client().unwrap(), store),
+        "" | "update" => Transport::Replay(store),
+        mode => panic!("unknown SEARCHED_FIXTURES mode {mode:?}"),
+    };
+
+    let mut config = Config::default();
+    config.plugins.pool_size = Some(1);
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    let eng = PluginEngine::new(reqwest::Client::new(), &config, &db)
+        .await
+        .unwrap()
+        .with_transport(transport);
+
+    let query = Query {
+        query: query.to_owned(),
+        page: 1,
+        ..Default::default()
+    };
+    let response = eng
+        .search_provider(query, provider)
+        .await
+        .unwrap_or_else(|err| panic!("{provider} failed: {err}"));
+
+    let expected_path = Path::new(FIXTURES).join(provider).join(expected);
+    if mode.is_empty() {
+        let expected: ProviderResponse =
+            serde_json::from_str(&fs::read_to_string(&expected_path).unwrap()).unwrap();
+        assert_eq!(response, expected, "{provider} results changed");
+    } else {
+        assert!(!response.results.is_empty(), "{provider} found nothing, not saving that");
+        fs::write(
+            &expected_path,
+            serde_json::to_string_pretty(&response).unwrap() + "\n",
+        )
+        .unwrap();
+    }
+}
+
+#[tokio::test]
+#[ignore = "no fixtures recorded yet"]
+async fn duckduckgo() {
+    check("duckduckgo", "rust programming language", "expected.json").await;
+}
+
+#[tokio::test]
+#[ignore = "no fixtures recorded yet"]
+async fn duckduckgo_correction() {
+    check("duckduckgo", "rust programing language", "expected_correction.json").await;
+}
+
+#[tokio::test]
+#[ignore = "no fixtures recorded yet"]
+async fn mojeek() {
+    check("mojeek", "rust programming language", "expected.json").await;
+}
+
+#[tokio::test]
+#[ignore = "no fixtures recorded yet"]
+async fn mojeek_correction() {
+    check("mojeek", "rust programing language", "expected_correction.json").await;
+}
+
+#[tokio::test]
+#[ignore = "no fixtures recorded yet"]
+async fn startpage() {
+    check("startpage", "rust programming language", "expected.json").await;
+}